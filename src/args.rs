@@ -1,11 +1,72 @@
-use clap::{Args, Parser, Subcommand};
+use crate::config::ConfigFormat;
+use crate::epicor::{ExpenseCode, LaborType, ListFormat};
+use chrono::NaiveDate;
+use clap::{ArgAction, Args, Parser, Subcommand, ValueEnum};
 use std::fmt;
 use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
 use std::str::FromStr;
 
 #[derive(Debug, Parser)]
 #[clap(author, version, about)]
 pub struct OmniArgs {
+    /// Print command output as JSON instead of formatted text
+    #[clap(long, global = true)]
+    pub json: bool,
+    /// Write the command's data (e.g. --json output) to this file instead of stdout
+    #[clap(short, long, global = true)]
+    pub output: Option<PathBuf>,
+    /// Print --json output on a single line (the default when output isn't a terminal)
+    #[clap(long, global = true, conflicts_with = "pretty")]
+    pub compact: bool,
+    /// Indent --json output even when it isn't going to a terminal
+    #[clap(long, global = true)]
+    pub pretty: bool,
+    /// Only print the command's data and errors, without status messages like "Login successful"
+    #[clap(short, long, global = true)]
+    pub quiet: bool,
+    /// Disable colored output (also disabled by NO_COLOR or when output isn't a terminal)
+    #[clap(long, global = true)]
+    pub no_color: bool,
+    /// Print diagnostic output to stderr (-v for requests, -vv for raw responses, -vvv for everything)
+    #[clap(short, long, global = true, action = ArgAction::Count)]
+    pub verbose: u8,
+    /// Print the Epicor requests that would be sent instead of sending them
+    #[clap(long, global = true)]
+    pub dry_run: bool,
+    /// Seconds to wait for Epicor before timing out (overrides EPICOR_TIMEOUT_SECS)
+    #[clap(long, global = true)]
+    pub timeout: Option<u64>,
+    /// Epicor base URL to use for this run (overrides EPICOR_BASE_URL)
+    #[clap(long, global = true)]
+    pub base_url: Option<String>,
+    /// Epicor REST API version to use for this run, e.g. v2 (overrides EPICOR_API_VERSION)
+    #[clap(long, global = true)]
+    pub api_version: Option<String>,
+    /// Epicor company to use for this run, e.g. 100 (overrides EPICOR_COMPANY)
+    #[clap(long, global = true)]
+    pub company: Option<String>,
+    /// Epicor API key to use for this run (overrides EPICOR_API_KEY)
+    #[clap(long, global = true)]
+    pub api_key: Option<String>,
+    /// Epicor basic auth header to use for this run (overrides EPICOR_BASIC_AUTH)
+    #[clap(long, global = true)]
+    pub basic_auth: Option<String>,
+    /// Skip TLS certificate checks for Epicor. Insecure, only for test environments
+    #[clap(long, global = true)]
+    pub danger_accept_invalid_certs: bool,
+    /// Reuse a case's status fetched within this many seconds (default 0, off)
+    #[clap(long, global = true, default_value = "0")]
+    pub cache_ttl: u64,
+    /// How to show Epicor dates, as a strftime format (default %Y-%m-%d)
+    #[clap(long, global = true)]
+    pub date_format: Option<String>,
+    /// Config file to use instead of the usual ones: a config.toml with profiles, or a .env file
+    #[clap(long, global = true)]
+    pub config: Option<PathBuf>,
+    /// Config profile to use (or, with setup, to write)
+    #[clap(long, global = true)]
+    pub profile: Option<String>,
     #[clap(subcommand)]
     pub entity_type: EntityType,
 }
@@ -19,6 +80,39 @@ pub enum EntityType {
     Bitwarden(BitwardenCommand),
     /// Interact with Epicor ERP
     Epicor(EpicorCommand),
+    /// Check Omni's configuration: the Bitwarden CLI, config files, settings and Epicor connection
+    Doctor,
+    /// Inspect Omni's settings
+    Config(ConfigCommand),
+}
+
+#[derive(Debug, Args)]
+pub struct ConfigCommand {
+    #[clap(subcommand)]
+    pub subcommand: ConfigSubcommand,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ConfigSubcommand {
+    /// Prints the effective settings after profiles, .env files and flags, with secrets masked
+    Show(ShowConfigCommand),
+    /// Changes one setting in the active profile or .env file, e.g. to rotate the Epicor API key
+    Set(SetConfigCommand),
+}
+
+#[derive(Debug, Args)]
+pub struct SetConfigCommand {
+    /// Name of the setting, e.g. EPICOR_API_KEY
+    pub key: String,
+    /// New value (or @file, or - to read it from stdin)
+    pub value: String,
+}
+
+#[derive(Debug, Args)]
+pub struct ShowConfigCommand {
+    /// Output format: env, json or toml (the global --json flag implies json)
+    #[clap(short, long, default_value = "env")]
+    pub format: ConfigFormat,
 }
 
 #[derive(Debug, Args)]
@@ -26,27 +120,64 @@ pub struct SetupCommand {
     /// BitWarden Client ID
     #[clap(short = 'i', long)]
     pub bw_client_id: Option<String>,
-    /// BitWarden Client Secret
+    /// BitWarden Client Secret (or @file, or - to read it from stdin)
     #[clap(short = 's', long)]
     pub bw_client_secret: Option<String>,
-    /// BitWarden Master Password
+    /// BitWarden Master Password (or @file, - for stdin, or set BW_MASTER_PASSWORD)
     #[clap(short = 'p', long)]
     pub bw_master_password: Option<String>,
     /// Epicor Base URL
     #[clap(short = 'u', long)]
     pub epicor_base_url: Option<String>,
-    /// Epicor API Key
+    /// Epicor API Key (or @file, or - to read it from stdin)
     #[clap(short = 'k', long)]
     pub epicor_api_key: Option<String>,
     /// Epicor Username
     #[clap(short = 'n', long)]
     pub epicor_username: Option<String>,
-    /// Epicor Password
+    /// Epicor Password (or @file, - for stdin, or set EPICOR_PASSWORD)
     #[clap(short = 'w', long)]
     pub epicor_password: Option<String>,
-    /// OpenAI API Key
-    #[clap(short = 'o', long)]
+    /// OpenAI API Key (or @file, or - to read it from stdin)
+    #[clap(short = 'O', long)]
     pub openai_api_key: Option<String>,
+    /// URL of a self-hosted Bitwarden or Vaultwarden server (defaults to bitwarden.com)
+    #[clap(long)]
+    pub bw_server: Option<String>,
+    /// Expected SHA-256 of the Bitwarden CLI zip, instead of the checksum Bitwarden publishes
+    /// (can also be set with BW_SHA256)
+    #[clap(long)]
+    pub bw_sha256: Option<String>,
+    /// Install the Bitwarden CLI to a system directory (needs admin rights) instead of ~/.local/bin
+    #[clap(long)]
+    pub system_install: bool,
+    /// Download the Bitwarden CLI even if a working copy is already installed
+    #[clap(long)]
+    pub force_download: bool,
+    /// Proxy for all outbound requests, e.g. http://proxy.example.com:8080
+    /// (HTTPS_PROXY, HTTP_PROXY and NO_PROXY are also honored)
+    #[clap(long)]
+    pub proxy: Option<String>,
+    /// Prompt for each value instead of passing flags (the default when no flags are given)
+    #[clap(long)]
+    pub interactive: bool,
+    /// Skip verifying the Epicor connection after setup (e.g. when offline)
+    #[clap(long)]
+    pub no_verify: bool,
+}
+
+impl SetupCommand {
+    /// Returns true if none of the setup values were passed as flags
+    pub fn is_empty(&self) -> bool {
+        self.bw_client_id.is_none()
+            && self.bw_client_secret.is_none()
+            && self.bw_master_password.is_none()
+            && self.epicor_base_url.is_none()
+            && self.epicor_api_key.is_none()
+            && self.epicor_username.is_none()
+            && self.epicor_password.is_none()
+            && self.openai_api_key.is_none()
+    }
 }
 
 #[derive(Debug, Args)]
@@ -58,11 +189,47 @@ pub struct BitwardenCommand {
 #[derive(Debug, Subcommand)]
 pub enum BitwardenSubcommand {
     /// Lists BitWarden Vault items
-    List,
+    List(ListCommand),
     /// Gets BitWarden Vault item
     Get(GetCommand),
     /// Creates BitWarden Vault item
     Create(CreateCommand),
+    /// Deletes BitWarden Vault item
+    Delete(DeleteCommand),
+    /// Edits BitWarden Vault item, changing only the provided fields
+    Edit(EditCommand),
+    /// Syncs the local vault with the Bitwarden server
+    Sync,
+    /// Locks the vault, logs out and clears the cached session
+    Logout,
+    /// Shows whether the vault is locked, unlocked or logged out, and which account is active
+    Status,
+    /// Generates a strong password or passphrase, optionally storing it as a new login item
+    Generate(GenerateCommand),
+    /// Saves a BitWarden Vault item attachment to the file given with --output
+    GetAttachment(GetAttachmentCommand),
+    /// Runs any bw command with the managed login and session, e.g. `raw -- list folders`
+    Raw(RawCommand),
+    /// Points the Bitwarden CLI at a self-hosted Bitwarden or Vaultwarden server
+    ConfigServer(ConfigServerCommand),
+}
+
+#[derive(Debug, Args)]
+pub struct ListCommand {
+    /// What to list (items|folders|collections|organizations), items by default
+    pub kind: Option<VaultListKind>,
+    /// Sync the vault before listing items
+    #[clap(long)]
+    pub sync: bool,
+    /// Only list items matching this search term
+    #[clap(short, long)]
+    pub search: Option<String>,
+    /// Only list items in the folder with this name
+    #[clap(short, long)]
+    pub folder: Option<String>,
+    /// Print just the names instead of the full JSON or id/name table
+    #[clap(long)]
+    pub names_only: bool,
 }
 
 #[derive(Debug, Args)]
@@ -73,6 +240,55 @@ pub struct GetCommand {
     /// Value of the vault item (e.g. CAEL10)
     #[clap(short, long)]
     pub name: String,
+    /// Sync the vault before getting the item
+    #[clap(long)]
+    pub sync: bool,
+    /// Copy the password or TOTP code to the clipboard instead of printing it
+    #[clap(short, long)]
+    pub copy: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ConfigServerCommand {
+    /// URL of the server, e.g. https://vault.example.com
+    pub url: String,
+}
+
+#[derive(Debug, Args)]
+pub struct RawCommand {
+    /// Arguments passed to bw as is
+    #[clap(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+    pub args: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct GetAttachmentCommand {
+    /// File name or id of the attachment
+    #[clap(short, long)]
+    pub name: String,
+    /// Id of the vault item the attachment belongs to
+    #[clap(short, long)]
+    pub item_id: String,
+}
+
+/// The kinds of vault objects `bw list` can list
+#[derive(Debug, PartialEq, Clone, Copy, ValueEnum)]
+pub enum VaultListKind {
+    Items,
+    Folders,
+    Collections,
+    Organizations,
+}
+
+impl Display for VaultListKind {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            VaultListKind::Items => write!(f, "items"),
+            VaultListKind::Folders => write!(f, "folders"),
+            VaultListKind::Collections => write!(f, "collections"),
+            VaultListKind::Organizations => write!(f, "organizations"),
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -147,8 +363,68 @@ pub struct CreateCommand {
     #[clap(short, long)]
     pub password: String,
     /// Notes of BitWarden Vault item
+    #[clap(short = 'N', long)]
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct GenerateCommand {
+    /// Length of the password
+    #[clap(long)]
+    pub length: Option<u32>,
+    /// Include uppercase characters
+    #[clap(long)]
+    pub uppercase: bool,
+    /// Include lowercase characters
+    #[clap(long)]
+    pub lowercase: bool,
+    /// Include numbers
+    #[clap(long)]
+    pub number: bool,
+    /// Include special characters
+    #[clap(long)]
+    pub special: bool,
+    /// Generate a passphrase of words instead of a password
+    #[clap(long)]
+    pub passphrase: bool,
+    /// Number of words in the passphrase
+    #[clap(long, requires = "passphrase")]
+    pub words: Option<u32>,
+    /// Separator between the words of the passphrase
+    #[clap(long, requires = "passphrase")]
+    pub separator: Option<String>,
+    /// Store the generated password as a new login item with this name instead of printing it
+    #[clap(long)]
+    pub create: Option<String>,
+    /// Username of the login item created with --create
+    #[clap(long, requires = "create")]
+    pub username: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct DeleteCommand {
+    /// ID of BitWarden Vault item
     #[clap(short, long)]
-    pub notes: String,
+    pub id: String,
+}
+
+#[derive(Debug, Args)]
+pub struct EditCommand {
+    /// ID of BitWarden Vault item
+    #[clap(short, long)]
+    pub id: String,
+    /// New name of BitWarden Vault item
+    #[clap(short, long)]
+    pub name: Option<String>,
+    /// New username of BitWarden Vault item
+    #[clap(short, long)]
+    pub username: Option<String>,
+    /// New password of BitWarden Vault item
+    #[clap(short, long)]
+    pub password: Option<String>,
+    /// New notes of BitWarden Vault item
+    #[clap(short = 'N', long)]
+    pub notes: Option<String>,
 }
 
 #[derive(Debug, Args)]
@@ -161,6 +437,62 @@ pub struct EpicorCommand {
 pub enum EpicorSubcommand {
     /// Interact with Epicor Cases
     Case(CaseCommand),
+    /// Interact with Epicor Time Entries
+    Time(TimeEntryCommand),
+    /// Check that Epicor is reachable and accepts the configured credentials
+    Ping,
+}
+
+#[derive(Debug, Args)]
+pub struct TimeEntryCommand {
+    #[clap(subcommand)]
+    pub subcommand: TimeEntrySubcommand,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum TimeEntrySubcommand {
+    /// Adds a time entry for an employee
+    Add(AddTimeEntryCommand),
+    /// Lists an employee's time entries for a day
+    List(ListTimeEntriesCommand),
+}
+
+#[derive(Debug, Args)]
+pub struct ListTimeEntriesCommand {
+    /// Epicor employee id
+    #[clap(short, long)]
+    pub employee_id: u32,
+    /// Day to list, as yyyy-mm-dd (defaults to today)
+    #[clap(short, long)]
+    pub date: Option<NaiveDate>,
+}
+
+#[derive(Debug, Args)]
+pub struct AddTimeEntryCommand {
+    /// Epicor employee id
+    #[clap(short, long)]
+    pub employee_id: u32,
+    /// Labor type (indirect|project|production|service|setup)
+    #[clap(short, long)]
+    pub labor_type: LaborType,
+    /// Project the time is booked against (required for project labor)
+    #[clap(short, long)]
+    pub project_id: Option<String>,
+    /// WBS phase the time is booked against
+    #[clap(short, long)]
+    pub wbs_phase_id: Option<String>,
+    /// Operation sequence the time is booked against
+    #[clap(short = 'O', long)]
+    pub operation: Option<u32>,
+    /// Plant the time is booked in (defaults to EPICOR_DEFAULT_PLANT)
+    #[clap(long)]
+    pub plant: Option<String>,
+    /// Expense code for the labor (direct-labor|indirect-labor)
+    #[clap(short = 'x', long)]
+    pub expense_code: Option<ExpenseCode>,
+    /// Number of hours worked
+    #[clap(short = 'H', long)]
+    pub hours: f32,
 }
 
 #[derive(Debug, Args)]
@@ -171,70 +503,324 @@ pub struct CaseCommand {
 
 #[derive(Debug, Subcommand)]
 pub enum CaseSubcommand {
+    /// Opens a new Epicor case
+    Create(CreateCaseCommand),
     /// Completes the current task for a given Epicor case
     CompleteTask(CompleteTaskCommand),
     /// Adds a comment to a given Epicor case
     AddComment(AddCommentCommand),
+    /// Replaces the most recent comment on a given Epicor case
+    EditComment(EditCommentCommand),
+    /// Attaches a file to a given Epicor case
+    AddAttachment(AddAttachmentCommand),
     /// Retrieves the current status of a given case
     GetStatus(GetStatusCommand),
     /// Gets a summary of the case comments
     GetCommentSummary(GetCommentSummaryCommand),
     /// Updates the Quote for a given case
     UpdateQuote(UpdateQuoteCommand),
+    /// Updates fields on a given case, changing only the provided fields
+    Update(UpdateCaseCommand),
+    /// Sets the requested delivery, start and expected delivery dates of a given case
+    SetDates(SetDatesCommand),
     /// Retrieves the last comment on the case
     GetLastComment(GetLastCommentCommand),
+    /// Reassigns the current task of a given case without completing it
+    Reassign(ReassignCommand),
+    /// Moves a given case to another WBS phase and operation
+    MovePhase(MovePhaseCommand),
+    /// Sets the estimated hours of a given case
+    SetEstimate(SetEstimateCommand),
+    /// Closes a given case once its task workflow is finished
+    Close(CloseCaseCommand),
+    /// Lists open cases, optionally filtered by assignee, project or owner
+    List(ListCasesCommand),
+    /// Polls the status of a given case and prints whenever its current task changes
+    Watch(WatchCommand),
+    /// Prints the link to a given case in the Epicor web client
+    OpenUrl(OpenUrlCommand),
+}
+
+#[derive(Debug, Args)]
+pub struct CreateCaseCommand {
+    /// Customer ID the case is for
+    #[clap(short, long)]
+    pub customer: String,
+    /// Description of the request
+    #[clap(short, long)]
+    pub description: String,
+    /// Project ID
+    #[clap(short, long)]
+    pub project: String,
+    /// Part number
+    #[clap(short = 'r', long)]
+    pub part_num: String,
+    /// Optional case owner
+    #[clap(short = 'O', long)]
+    pub owner: Option<String>,
 }
 
 #[derive(Debug, Args)]
 pub struct GetLastCommentCommand {
     /// Epicor case number
-    #[clap(short = 'n', long)]
+    #[clap(short = 'n', long, value_parser = parse_case_number)]
     pub case_number: u32,
 }
 
 #[derive(Debug, Args)]
 pub struct CompleteTaskCommand {
     /// Epicor case number
-    #[clap(short = 'n', long)]
+    #[clap(
+        short = 'n',
+        long,
+        value_parser = parse_case_number,
+        required_unless_present = "from_file"
+    )]
+    pub case_number: Option<u32>,
+    /// Who the next task should be assigned to (the default for cases in --from-file)
+    #[clap(short, long, required_unless_present_any = ["from_file", "to_me", "close_if_final"])]
+    pub assign_to: Option<String>,
+    /// Assign the next task to yourself, as configured with EPICOR_DEFAULT_ASSIGNEE
+    #[clap(long, conflicts_with = "assign_to")]
+    pub to_me: bool,
+    /// Complete the task on every case in this file, with one case number per line
+    /// or case,assign_to CSV lines
+    #[clap(long, conflicts_with = "case_number")]
+    pub from_file: Option<PathBuf>,
+    /// Stop at the first case in --from-file that fails instead of carrying on
+    #[clap(long, requires = "from_file")]
+    pub fail_fast: bool,
+    /// Optional comment to add to the case
+    #[clap(short, long)]
+    pub comment: Option<String>,
+    /// Close the case if this was its final task. --assign-to is then only needed in case it
+    /// wasn't.
+    #[clap(long, conflicts_with = "from_file")]
+    pub close_if_final: bool,
+    /// Complete the task without asking for confirmation
+    #[clap(short, long)]
+    pub yes: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct CloseCaseCommand {
+    /// Epicor case number
+    #[clap(short = 'n', long, value_parser = parse_case_number)]
     pub case_number: u32,
-    /// Who the next task should be assigned to
+    /// Optional comment to add when closing the case
+    #[clap(short, long)]
+    pub comment: Option<String>,
+    /// Close the case even if it still has an active task
+    #[clap(long)]
+    pub force: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ReassignCommand {
+    /// Epicor case number
+    #[clap(short = 'n', long, value_parser = parse_case_number)]
+    pub case_number: u32,
+    /// Who the current task should be assigned to
     #[clap(short, long)]
     pub assign_to: String,
-    /// Optional comment to add to the case
+}
+
+#[derive(Debug, Args)]
+pub struct SetEstimateCommand {
+    /// Epicor case number
+    #[clap(short = 'n', long, value_parser = parse_case_number)]
+    pub case_number: u32,
+    /// Estimated hours for the case
+    #[clap(short = 'H', long, value_parser = parse_estimated_hours)]
+    pub hours: f64,
+}
+
+#[derive(Debug, Args)]
+pub struct MovePhaseCommand {
+    /// Epicor case number
+    #[clap(short = 'n', long, value_parser = parse_case_number)]
+    pub case_number: u32,
+    /// WBS phase ID to move the case to
     #[clap(short, long)]
-    pub comment: Option<String>,
+    pub phase: String,
+    /// WBS phase operation to move the case to
+    #[clap(short = 'O', long, value_parser = parse_phase_op)]
+    pub op: i32,
+}
+
+#[derive(Debug, Args)]
+pub struct ListCasesCommand {
+    /// Only list cases whose current task is assigned to this person
+    #[clap(short, long)]
+    pub assigned_to: Option<String>,
+    /// Only list cases for this project
+    #[clap(short, long)]
+    pub project: Option<String>,
+    /// Only list cases owned by this person
+    #[clap(short = 'O', long)]
+    pub owner: Option<String>,
+    /// Maximum number of cases to list
+    #[clap(short, long, default_value_t = 50)]
+    pub limit: u32,
+    /// Output format: table, json or csv (the global --json flag implies json)
+    #[clap(short, long, default_value = "table")]
+    pub format: ListFormat,
+}
+
+#[derive(Debug, Args)]
+pub struct EditCommentCommand {
+    /// Epicor case number
+    #[clap(short = 'n', long, value_parser = parse_case_number)]
+    pub case_number: u32,
+    /// Comment to replace the last comment with
+    #[clap(short, long)]
+    pub comment: String,
 }
 
 #[derive(Debug, Args)]
 pub struct AddCommentCommand {
     /// Epicor case number
-    #[clap(short = 'n', long)]
+    #[clap(short = 'n', long, value_parser = parse_case_number)]
     pub case_number: u32,
     /// Comment to add to the case
     #[clap(short, long)]
     pub comment: String,
+    /// Skip adding the comment if it's already the case's last comment
+    #[clap(long)]
+    pub dedupe: bool,
 }
 
 #[derive(Debug, Args)]
-pub struct GetStatusCommand {
+pub struct AddAttachmentCommand {
     /// Epicor case number
-    #[clap(short = 'n', long)]
+    #[clap(short = 'n', long, value_parser = parse_case_number)]
     pub case_number: u32,
+    /// Path of the file to attach
+    #[clap(short, long)]
+    pub file: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct GetStatusCommand {
+    /// Epicor case number, repeat it to get the status of several cases at once
+    #[clap(short = 'n', long, value_parser = parse_case_number, required = true)]
+    pub case_number: Vec<u32>,
+    /// How many cases to fetch at the same time
+    #[clap(long, default_value = "4")]
+    pub concurrency: usize,
+    /// Stop at the first case that fails instead of fetching the rest
+    #[clap(long)]
+    pub fail_fast: bool,
 }
 
 #[derive(Debug, Args)]
 pub struct GetCommentSummaryCommand {
     /// Epicor case number
-    #[clap(short = 'n', long)]
+    #[clap(short = 'n', long, value_parser = parse_case_number)]
     pub case_number: u32,
+    /// Summarize the comments with OpenAI (requires OPENAI_API_KEY)
+    #[clap(long)]
+    pub ai: bool,
+    /// Only show the most recent N comments
+    #[clap(short, long)]
+    pub limit: Option<usize>,
+    /// Only show comments made on or after this date (YYYY-MM-DD)
+    #[clap(long)]
+    pub since: Option<NaiveDate>,
+}
+
+#[derive(Debug, Args)]
+pub struct WatchCommand {
+    /// Epicor case number
+    #[clap(short = 'n', long, value_parser = parse_case_number)]
+    pub case_number: u32,
+    /// Seconds to wait between status checks
+    #[clap(short, long, default_value_t = 30)]
+    pub interval: u64,
+    /// Stop watching once the case has no remaining tasks
+    #[clap(long)]
+    pub until_complete: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct OpenUrlCommand {
+    /// Epicor case number
+    #[clap(short = 'n', long, value_parser = parse_case_number)]
+    pub case_number: u32,
+    /// Open the link in the default browser as well
+    #[clap(long)]
+    pub open: bool,
 }
 
 #[derive(Debug, Args)]
 pub struct UpdateQuoteCommand {
     /// Epicor case number
-    #[clap(short = 'c', long)]
+    #[clap(short = 'c', long, value_parser = parse_case_number)]
     pub case_number: u32,
     /// New Quantity for the Case Part (used to update quote)
     #[clap(short = 'n', long)]
     pub new_quantity: f32,
+    /// Allow a zero or negative quantity, which is otherwise rejected as a likely mistake
+    #[clap(long)]
+    pub allow_zero: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct UpdateCaseCommand {
+    /// Epicor case number
+    #[clap(short = 'n', long, value_parser = parse_case_number)]
+    pub case_number: u32,
+    /// New Quantity for the Case Part
+    #[clap(short = 'Q', long)]
+    pub qty: Option<f32>,
+    /// New Unit Price for the Case Part
+    #[clap(short, long)]
+    pub unit_price: Option<f64>,
+    /// New Part Number for the case
+    #[clap(short, long)]
+    pub part_num: Option<String>,
+    /// New Requested Delivery date, as yyyy-mm-dd
+    #[clap(short, long)]
+    pub requested_delivery: Option<NaiveDate>,
+    /// New Expected Delivery date, as yyyy-mm-dd
+    #[clap(short, long)]
+    pub expected_delivery: Option<NaiveDate>,
+}
+
+#[derive(Debug, Args)]
+pub struct SetDatesCommand {
+    /// Epicor case number
+    #[clap(short = 'n', long, value_parser = parse_case_number)]
+    pub case_number: u32,
+    /// New requested delivery date, as yyyy-mm-dd
+    #[clap(short, long)]
+    pub requested: Option<NaiveDate>,
+    /// New start date, as yyyy-mm-dd
+    #[clap(short, long)]
+    pub start: Option<NaiveDate>,
+    /// New expected delivery date, as yyyy-mm-dd
+    #[clap(short, long)]
+    pub expected: Option<NaiveDate>,
+}
+
+/// Epicor case numbers start at 1, and Epicor answers 0 with a confusing "not found"
+pub fn parse_case_number(s: &str) -> Result<u32, String> {
+    match s.parse::<u32>() {
+        Ok(case_number) if case_number > 0 => Ok(case_number),
+        _ => Err("Case number must be a positive integer".to_string()),
+    }
+}
+
+fn parse_estimated_hours(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(hours) if hours.is_finite() && hours >= 0.0 => Ok(hours),
+        _ => Err("Estimated hours must be a non-negative number".to_string()),
+    }
+}
+
+fn parse_phase_op(s: &str) -> Result<i32, String> {
+    match s.parse::<i32>() {
+        Ok(op) if op >= 0 => Ok(op),
+        _ => Err("Phase operation must be a non-negative integer".to_string()),
+    }
 }
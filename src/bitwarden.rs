@@ -1,49 +1,137 @@
-use crate::args::VaultItemType;
+use crate::args::{GenerateCommand, ListCommand, VaultItemType, VaultListKind};
+use crate::config::{config_dir, required_env, required_secret, write_private_file};
+use crate::output::{data_println, print_fields, print_table, status_println, to_json};
+use crate::setup::generate_basic_auth;
 use anyhow::{anyhow, Result};
-use clap::{arg, command, Command as ClapCommand, Parser, Subcommand};
-use dotenv::dotenv;
+use base64::engine::general_purpose;
+use base64::Engine;
+use colored::Colorize;
+use fs2::FileExt;
 use regex::Regex;
+use reqwest::Url;
+use serde::Deserialize;
+use serde_json::{json, Value};
 use std::env;
-use std::process::Command;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// The parts of `bw status` shown by `omni bitwarden status`
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct VaultStatus {
+    server_url: Option<String>,
+    last_sync: Option<String>,
+    user_email: Option<String>,
+    status: String,
+}
+
+/// Explains why bw couldn't be run, which is almost always because it isn't installed
+fn bw_command_error(e: io::Error) -> anyhow::Error {
+    if e.kind() == io::ErrorKind::NotFound {
+        anyhow!("Bitwarden CLI 'bw' not found on PATH — run `omni setup` to install it")
+    } else {
+        anyhow!("Failed to run the Bitwarden CLI: {}", e)
+    }
+}
 
 fn login() -> Result<()> {
-    let bw_clientid = env::var("BW_CLIENTID").map_err(|_| anyhow!("Failed to get BW_CLIENTID"))?;
-    let bw_clientsecret =
-        env::var("BW_CLIENTSECRET").map_err(|_| anyhow!("Failed to get BW_CLIENTSECRET"))?;
+    let bw_clientid = required_env("BW_CLIENTID")?;
+    let bw_clientsecret = required_secret("BW_CLIENTSECRET")?;
+
+    // bw remembers its server, but it has to be set again if its data was reset
+    if let Ok(server_url) = env::var("BW_SERVER") {
+        set_server(&server_url)?;
+    }
 
     env::set_var("BW_CLIENTID", bw_clientid);
-    env::set_var("BW_CLIENTSECRET", bw_clientsecret);
+    env::set_var("BW_CLIENTSECRET", &bw_clientsecret);
 
     let login_output = Command::new("bw")
         .arg("login")
         .arg("--apikey")
         .output()
-        .expect("Failed to execute command");
+        .map_err(bw_command_error)?;
 
     if !login_output.status.success() {
-        return Err(anyhow!("Failed to login with API key"));
+        return Err(anyhow!(
+            "Failed to login with API key: {}",
+            redact(&stderr_message(&login_output), &bw_clientsecret)
+        ));
     }
 
-    println!("Login successful");
+    status_println!("Login successful");
+
+    Ok(())
+}
+
+/// Runs `bw config server`, which bw only allows while logged out
+fn set_server(server_url: &str) -> Result<()> {
+    let config_output = Command::new("bw")
+        .arg("config")
+        .arg("server")
+        .arg(server_url)
+        .output()
+        .map_err(bw_command_error)?;
+
+    if !config_output.status.success() {
+        return Err(anyhow!(
+            "Failed to set the Bitwarden server to {}: {}",
+            server_url,
+            stderr_message(&config_output)
+        ));
+    }
+
+    Ok(())
+}
+
+/// Points bw at a self-hosted server, logging out first if needed since bw won't switch servers
+/// while logged in
+pub fn config_server(server_url: &str) -> Result<()> {
+    match Url::parse(server_url) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") => {}
+        _ => {
+            return Err(anyhow!(
+                "Invalid Bitwarden server URL '{}', expected e.g. https://vault.example.com",
+                server_url
+            ))
+        }
+    }
+
+    let _vault_lock = lock_vault_access()?;
+
+    if is_logged_in() {
+        status_println!("Logging out of Bitwarden to switch servers");
+        close_session()?;
+    }
+
+    set_server(server_url)?;
+
+    status_println!("Bitwarden CLI now uses {}", server_url);
 
     Ok(())
 }
 
 fn unlock_vault() -> Result<()> {
-    let master_password =
-        env::var("MASTER_PASSWORD").map_err(|_| anyhow!("Failed to get MASTER_PASSWORD"))?;
+    let master_password = required_secret("MASTER_PASSWORD")?;
 
     let unlock_output = Command::new("bw")
         .arg("unlock")
         .arg(&master_password)
         .output()
-        .expect("Failed to execute command");
+        .map_err(bw_command_error)?;
 
     if !unlock_output.status.success() {
-        return Err(anyhow!("Failed to unlock vault"));
+        return Err(anyhow!(
+            "Failed to unlock vault: {}",
+            redact(&stderr_message(&unlock_output), &master_password)
+        ));
     }
 
-    println!("Unlock successful");
+    status_println!("Unlock successful");
 
     let output =
         String::from_utf8(unlock_output.stdout).map_err(|_| anyhow!("Failed to parse output"))?;
@@ -69,13 +157,16 @@ fn lock_vault() -> Result<()> {
     let lock_output = Command::new("bw")
         .arg("lock")
         .output()
-        .expect("Failed to execute command");
+        .map_err(bw_command_error)?;
 
     if !lock_output.status.success() {
-        return Err(anyhow!("Failed to lock vault"));
+        return Err(anyhow!(
+            "Failed to lock vault: {}",
+            stderr_message(&lock_output)
+        ));
     }
 
-    println!("Lock successful");
+    status_println!("Lock successful");
 
     Ok(())
 }
@@ -84,80 +175,874 @@ fn logout() -> Result<()> {
     let logout_output = Command::new("bw")
         .arg("logout")
         .output()
-        .expect("Failed to execute command");
+        .map_err(bw_command_error)?;
 
     if !logout_output.status.success() {
-        return Err(anyhow!("Failed to logout"));
+        return Err(anyhow!("Failed to logout: {}", stderr_message(&logout_output)));
     }
 
-    println!("Logout successful");
+    status_println!("Logout successful");
 
     Ok(())
 }
 
-pub fn list_items() -> Result<()> {
+/// Returns the trimmed stderr of a finished bw command, which holds bw's error message
+fn stderr_message(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).trim().to_string()
+}
+
+/// Masks a secret in a message before it's shown, in case bw echoes it back
+fn redact(message: &str, secret: &str) -> String {
+    if secret.is_empty() {
+        return message.to_string();
+    }
+
+    message.replace(secret, "[redacted]")
+}
+
+/// bw reports a missing item with a "Not found." message
+fn is_not_found(output: &Output) -> bool {
+    stderr_message(output).contains("Not found")
+}
+
+fn is_logged_in() -> bool {
+    Command::new("bw")
+        .arg("login")
+        .arg("--check")
+        .output()
+        .map(|output| output.status.success())
+        .unwrap_or(false)
+}
+
+fn is_unlocked() -> bool {
+    Command::new("bw")
+        .arg("unlock")
+        .arg("--check")
+        .output()
+        .map(|output| output.status.success())
+        .unwrap_or(false)
+}
+
+fn session_file_path() -> Result<PathBuf> {
+    Ok(config_dir()?.join("bw_session"))
+}
+
+fn load_cached_session() -> Option<String> {
+    let session_key = fs::read_to_string(session_file_path().ok()?).ok()?;
+    let session_key = session_key.trim();
+
+    if session_key.is_empty() {
+        return None;
+    }
+
+    Some(session_key.to_string())
+}
+
+fn save_session(session_key: &str) -> Result<()> {
+    let session_file_path = session_file_path()?;
+
+    if let Some(parent) = session_file_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    // Ensure that only the current user can read the session key
+    write_private_file(&session_file_path, session_key)
+}
+
+/// How long to wait for another Omni run to finish with the vault before giving up
+const VAULT_LOCK_TIMEOUT_SECS: u64 = 60;
+
+/// Takes the lock that serializes vault access across Omni runs, so one run can't lock the vault
+/// or replace BW_SESSION while another is still using it. The lock is released when the returned
+/// file is closed.
+fn lock_vault_access() -> Result<File> {
+    let lock_file_path = config_dir()?.join("bw.lock");
+
+    if let Some(parent) = lock_file_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let lock_file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_file_path)?;
+
+    let started = Instant::now();
+    let mut waiting = false;
+
+    loop {
+        match lock_file.try_lock_exclusive() {
+            Ok(()) => return Ok(lock_file),
+            Err(e) if e.kind() == fs2::lock_contended_error().kind() => {}
+            Err(e) => return Err(anyhow!("Failed to lock {}: {}", lock_file_path.display(), e)),
+        }
+
+        if started.elapsed() >= Duration::from_secs(VAULT_LOCK_TIMEOUT_SECS) {
+            return Err(anyhow!(
+                "Timed out after {}s waiting for another Omni command to finish with the Bitwarden vault. If none is running, delete {} and try again.",
+                VAULT_LOCK_TIMEOUT_SECS,
+                lock_file_path.display()
+            ));
+        }
+
+        if !waiting {
+            eprintln!("Waiting for another Omni command to finish with the Bitwarden vault...");
+            waiting = true;
+        }
+
+        thread::sleep(Duration::from_millis(250));
+    }
+}
+
+fn clear_cached_session() -> Result<()> {
+    let session_file_path = session_file_path()?;
+
+    if session_file_path.exists() {
+        fs::remove_file(session_file_path)?;
+    }
+
+    Ok(())
+}
+
+/// Holds the vault lock for the command using the session, so other Omni runs wait for the
+/// guard to be dropped before touching the vault. If the command panics part way through, the
+/// vault is locked and logged out rather than left unlocked. A command that returns an error
+/// keeps the session cached, since errors like a missing item say nothing about the session.
+#[must_use]
+struct SessionGuard {
+    _vault_lock: File,
+}
+
+impl Drop for SessionGuard {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            if let Err(e) = close_session() {
+                eprintln!("Warning: failed to lock the vault: {}", e);
+            }
+        }
+    }
+}
+
+fn start_session() -> Result<SessionGuard> {
+    // Held until the guard is dropped, so the session can't be ended from under this run
+    let vault_lock = lock_vault_access()?;
+
+    // Reuse the cached session if the vault is still unlocked with it
+    if let Some(session_key) = load_cached_session() {
+        env::set_var("BW_SESSION", &session_key);
+
+        if is_unlocked() {
+            return Ok(SessionGuard {
+                _vault_lock: vault_lock,
+            });
+        }
+    }
+
+    // A failed login or unlock leaves the session unusable, so end it rather than cache it
+    if let Err(e) = login_and_unlock() {
+        if let Err(close_error) = close_session() {
+            eprintln!("Warning: failed to lock the vault: {}", close_error);
+        }
+
+        return Err(e);
+    }
+
+    // Cache the session so the next command doesn't have to unlock again
+    let session_key = env::var("BW_SESSION").map_err(|_| anyhow!("Failed to get BW_SESSION"))?;
+    save_session(&session_key)?;
+
+    Ok(SessionGuard {
+        _vault_lock: vault_lock,
+    })
+}
+
+fn login_and_unlock() -> Result<()> {
     // Login to vault
-    login()?;
+    if !is_logged_in() {
+        login()?;
+    }
 
     // Unlock vault
-    unlock_vault()?;
+    unlock_vault()
+}
 
-    let list_output = Command::new("bw")
-        .arg("list")
-        .arg("items")
-        .output()
-        .expect("Failed to execute list command for bitwarden vault");
+pub fn end_session() -> Result<()> {
+    let _vault_lock = lock_vault_access()?;
 
-    if !list_output.status.success() {
+    close_session()
+}
+
+/// Clears the cached session, then locks the vault and logs out. Callers must hold the vault lock.
+fn close_session() -> Result<()> {
+    clear_cached_session()?;
+
+    if is_logged_in() {
         // Lock vault
         lock_vault()?;
 
         // Logout of vault
         logout()?;
-        return Err(anyhow!("Failed to list vault items"));
     }
 
-    println!("{}", String::from_utf8(list_output.stdout).unwrap());
+    Ok(())
+}
+
+fn run_sync() -> Result<()> {
+    let sync_output = Command::new("bw")
+        .arg("sync")
+        .output()
+        .map_err(bw_command_error)?;
+
+    if !sync_output.status.success() {
+        return Err(anyhow!(
+            "Failed to sync vault: {}",
+            stderr_message(&sync_output)
+        ));
+    }
+
+    status_println!("Sync successful");
 
-    // Lock vault
-    lock_vault()?;
+    Ok(())
+}
+
+/// Syncs before a read when requested. A failed sync only warns, since the local vault data can still be read.
+fn sync_before_read(sync: bool) {
+    if sync {
+        if let Err(e) = run_sync() {
+            eprintln!("Warning: {}. Continuing with local vault data.", e);
+        }
+    }
+}
 
-    // Logout of vault
-    logout()?;
+pub fn sync_vault() -> Result<()> {
+    // Reuse the cached vault session or start a new one
+    let _session = start_session()?;
+
+    run_sync()?;
 
     Ok(())
 }
 
-pub fn get_item(item_type: &VaultItemType, item_name: &str) -> Result<()> {
-    // Login to vault
-    login()?;
+/// Looks up the id of the vault folder with the given name, as `bw list items --folderid` needs
+fn folder_id(folder_name: &str) -> Result<String> {
+    let list_output = Command::new("bw")
+        .arg("list")
+        .arg("folders")
+        .arg("--search")
+        .arg(folder_name)
+        .output()
+        .map_err(bw_command_error)?;
 
-    // Unlock vault
-    unlock_vault()?;
+    if !list_output.status.success() {
+        return Err(anyhow!(
+            "Failed to list vault folders: {}",
+            stderr_message(&list_output)
+        ));
+    }
+
+    let folders: Vec<Value> = serde_json::from_slice(&list_output.stdout)
+        .map_err(|_| anyhow!("Failed to parse vault folders"))?;
+
+    // The search also matches partial names, so pick out the exact one
+    let folder = folders
+        .iter()
+        .find(|folder| {
+            folder["name"]
+                .as_str()
+                .is_some_and(|name| name.eq_ignore_ascii_case(folder_name))
+        })
+        .ok_or(anyhow!("No vault folder found named {}", folder_name))?;
+
+    // Items outside any folder are in the "No Folder" folder, which has a null id
+    Ok(folder["id"].as_str().unwrap_or("null").to_string())
+}
+
+pub fn list_items(options: &ListCommand) -> Result<()> {
+    let kind = options.kind.unwrap_or(VaultListKind::Items);
+
+    if kind != VaultListKind::Items {
+        if options.folder.is_some() {
+            return Err(anyhow!("--folder only applies when listing items"));
+        }
+
+        return list_ids(kind, options);
+    }
+
+    // Reuse the cached vault session or start a new one
+    let _session = start_session()?;
+
+    sync_before_read(options.sync);
+
+    let mut list_command = Command::new("bw");
+    list_command.arg("list").arg("items");
+
+    if let Some(search) = &options.search {
+        list_command.arg("--search").arg(search);
+    }
+
+    if let Some(folder) = &options.folder {
+        list_command.arg("--folderid").arg(folder_id(folder)?);
+    }
+
+    // Picking out the names needs the whole list parsed, so it can't be streamed
+    if options.names_only {
+        let list_output = list_command
+            .output()
+            .map_err(bw_command_error)?;
+
+        if !list_output.status.success() {
+            return Err(anyhow!(
+                "Failed to list vault items: {}",
+                stderr_message(&list_output)
+            ));
+        }
+
+        let items: Vec<Value> = serde_json::from_slice(&list_output.stdout)
+            .map_err(|_| anyhow!("Failed to parse vault items"))?;
+
+        for name in items.iter().filter_map(|item| item["name"].as_str()) {
+            data_println!("{}", name);
+        }
+
+        return Ok(());
+    }
+
+    // Stream the items out as bw prints them, rather than holding a large vault in memory
+    let mut list_process = list_command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(bw_command_error)?;
+
+    if let Some(stdout) = list_process.stdout.take() {
+        let mut reader = BufReader::new(stdout);
+        let mut line = Vec::new();
+
+        while reader.read_until(b'\n', &mut line)? > 0 {
+            data_println!("{}", String::from_utf8_lossy(&line).trim_end_matches(['\r', '\n']));
+            line.clear();
+        }
+    }
+
+    // stdout has been taken, so this only collects the exit status and stderr
+    let list_output = list_process.wait_with_output()?;
+
+    if !list_output.status.success() {
+        return Err(anyhow!(
+            "Failed to list vault items: {}",
+            stderr_message(&list_output)
+        ));
+    }
+
+    Ok(())
+}
+
+/// Lists vault folders, collections or organizations as an id/name table, so their ids can be
+/// looked up for other commands
+fn list_ids(kind: VaultListKind, options: &ListCommand) -> Result<()> {
+    // Reuse the cached vault session or start a new one
+    let _session = start_session()?;
+
+    sync_before_read(options.sync);
+
+    let mut list_command = Command::new("bw");
+    list_command.arg("list").arg(kind.to_string());
+
+    if let Some(search) = &options.search {
+        list_command.arg("--search").arg(search);
+    }
+
+    let list_output = list_command.output().map_err(bw_command_error)?;
+
+    if !list_output.status.success() {
+        return Err(anyhow!(
+            "Failed to list vault {}: {}",
+            kind,
+            stderr_message(&list_output)
+        ));
+    }
+
+    let objects: Vec<Value> = serde_json::from_slice(&list_output.stdout)
+        .map_err(|_| anyhow!("Failed to parse vault {}", kind))?;
+
+    let rows: Vec<[String; 2]> = objects
+        .iter()
+        .map(|object| {
+            [
+                // The "No Folder" folder has a null id
+                object["id"].as_str().unwrap_or("null").to_string(),
+                object["name"].as_str().unwrap_or_default().to_string(),
+            ]
+        })
+        .collect();
+
+    if options.names_only {
+        for [_, name] in &rows {
+            data_println!("{}", name);
+        }
+    } else {
+        print_table(["Id", "Name"], &rows);
+    }
+
+    Ok(())
+}
+
+/// TOTP codes are valid for a 30 second window
+const TOTP_PERIOD_SECS: u64 = 30;
+
+pub fn get_item(item_type: &VaultItemType, item_name: &str, sync: bool, copy: bool) -> Result<()> {
+    // Attachments are often binary, so they have to be written to a file rather than printed
+    if *item_type == VaultItemType::Attachment {
+        return Err(anyhow!(
+            "Use `omni bitwarden get-attachment --item-id [id] --output [path]` to save attachments"
+        ));
+    }
+
+    if copy && !matches!(item_type, VaultItemType::Password | VaultItemType::Totp) {
+        return Err(anyhow!("--copy only works for password and totp items"));
+    }
+
+    // Reuse the cached vault session or start a new one
+    let _session = start_session()?;
+
+    sync_before_read(sync);
 
     let get_output = Command::new("bw")
         .arg("get")
         .arg(item_type.to_string())
         .arg(item_name)
         .output()
-        .expect("Failed to execute get command for bitwarden vault");
+        .map_err(bw_command_error)?;
 
     if !get_output.status.success() {
-        // Lock vault
-        lock_vault()?;
+        if is_not_found(&get_output) {
+            return Err(anyhow!("No vault item found named {}", item_name));
+        }
 
-        // Logout of vault
-        logout()?;
-        return Err(anyhow!("Failed to get vault item"));
+        return Err(anyhow!(
+            "Failed to get vault item: {}",
+            stderr_message(&get_output)
+        ));
+    }
+
+    let value = String::from_utf8_lossy(&get_output.stdout);
+
+    if copy {
+        copy_to_clipboard(value.trim())?;
+
+        if *item_type == VaultItemType::Totp {
+            status_println!("Copied to clipboard (expires in {}s)", totp_seconds_left());
+        } else {
+            status_println!("Copied to clipboard");
+        }
+    } else {
+        data_println!("{}", value);
+    }
+
+    Ok(())
+}
+
+fn copy_to_clipboard(value: &str) -> Result<()> {
+    arboard::Clipboard::new()
+        .and_then(|mut clipboard| clipboard.set_text(value))
+        .map_err(|e| anyhow!("Failed to copy to the clipboard: {}", e))
+}
+
+/// Seconds until the current TOTP code is replaced by the next one
+fn totp_seconds_left() -> u64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    TOTP_PERIOD_SECS - now % TOTP_PERIOD_SECS
+}
+
+pub fn get_attachment(name: &str, item_id: &str, output: &Path) -> Result<()> {
+    // Reuse the cached vault session or start a new one
+    let _session = start_session()?;
+
+    // bw writes the attachment to disk itself, so the bytes are never decoded as text
+    let get_output = Command::new("bw")
+        .arg("get")
+        .arg("attachment")
+        .arg(name)
+        .arg("--itemid")
+        .arg(item_id)
+        .arg("--output")
+        .arg(output)
+        .output()
+        .map_err(bw_command_error)?;
+
+    if !get_output.status.success() {
+        if is_not_found(&get_output) {
+            return Err(anyhow!("No attachment {} found on vault item {}", name, item_id));
+        }
+
+        return Err(anyhow!(
+            "Failed to get attachment: {}",
+            stderr_message(&get_output)
+        ));
+    }
+
+    status_println!("Saved attachment {} to {}", name, output.display());
+
+    Ok(())
+}
+
+/// Runs bw with the given arguments inside the managed session, passing its input and output
+/// straight through so interactive commands work too
+pub fn run_raw(args: &[String]) -> Result<()> {
+    // These would fight with the session Omni manages
+    if let Some(command @ ("login" | "logout" | "lock" | "unlock")) =
+        args.first().map(|command| command.as_str())
+    {
+        return Err(anyhow!(
+            "Omni logs in and unlocks the vault itself, so `bw {}` can't be run through raw. Use `omni bitwarden logout` to end the session.",
+            command
+        ));
+    }
+
+    // Reuse the cached vault session or start a new one
+    let _session = start_session()?;
+
+    let status = Command::new("bw")
+        .args(args)
+        .status()
+        .map_err(bw_command_error)?;
+
+    if !status.success() {
+        return Err(anyhow!("bw {} failed ({})", args.join(" "), status));
+    }
+
+    Ok(())
+}
+
+pub fn create_item(name: &str, username: &str, password: &str, notes: Option<&str>) -> Result<()> {
+    // Reuse the cached vault session or start a new one
+    let _session = start_session()?;
+
+    // Bitwarden expects null rather than an empty string when an item has no notes
+    let notes = match notes {
+        Some(notes) if !notes.trim().is_empty() => Value::String(notes.to_string()),
+        _ => Value::Null,
+    };
+
+    // Build the login item template (type 1 is a login item)
+    let item = json!({
+        "type": 1,
+        "name": name,
+        "notes": notes,
+        "login": {
+            "username": username,
+            "password": password,
+        },
+    });
+
+    let encoded_item = general_purpose::STANDARD.encode(item.to_string());
+
+    let mut create_process = Command::new("bw")
+        .arg("create")
+        .arg("item")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(bw_command_error)?;
+
+    // Pipe the encoded item to bw, dropping stdin afterwards so bw sees EOF
+    if let Some(mut stdin) = create_process.stdin.take() {
+        stdin
+            .write_all(encoded_item.as_bytes())
+            .map_err(|e| anyhow!("Failed to write item to the Bitwarden create command: {}", e))?;
+    }
+
+    let create_output = create_process.wait_with_output().map_err(bw_command_error)?;
+
+    if !create_output.status.success() {
+        return Err(anyhow!(
+            "Failed to create vault item: {}",
+            stderr_message(&create_output)
+        ));
+    }
+
+    status_println!("Created vault item {}", name);
+
+    Ok(())
+}
+
+pub fn delete_item(id: &str) -> Result<()> {
+    // Reuse the cached vault session or start a new one
+    let _session = start_session()?;
+
+    let delete_output = Command::new("bw")
+        .arg("delete")
+        .arg("item")
+        .arg(id)
+        .output()
+        .map_err(bw_command_error)?;
+
+    if !delete_output.status.success() {
+        if is_not_found(&delete_output) {
+            return Err(anyhow!("No vault item found with id {}", id));
+        }
+
+        return Err(anyhow!(
+            "Failed to delete vault item: {}",
+            stderr_message(&delete_output)
+        ));
+    }
+
+    status_println!("Deleted vault item {}", id);
+
+    Ok(())
+}
+
+pub fn edit_item(
+    id: &str,
+    name: Option<&str>,
+    username: Option<&str>,
+    password: Option<&str>,
+    notes: Option<&str>,
+) -> Result<()> {
+    // Reuse the cached vault session or start a new one
+    let _session = start_session()?;
+
+    // Fetch the current item so that only the provided fields are changed
+    let get_output = Command::new("bw")
+        .arg("get")
+        .arg("item")
+        .arg(id)
+        .output()
+        .map_err(bw_command_error)?;
+
+    if !get_output.status.success() {
+        if is_not_found(&get_output) {
+            return Err(anyhow!("No vault item found with id {}", id));
+        }
+
+        return Err(anyhow!(
+            "Failed to get vault item: {}",
+            stderr_message(&get_output)
+        ));
+    }
+
+    let mut item: Value = serde_json::from_slice(&get_output.stdout)
+        .map_err(|_| anyhow!("Failed to parse vault item {}", id))?;
+
+    if let Some(name) = name {
+        item["name"] = json!(name);
+    }
+
+    if let Some(username) = username {
+        item["login"]["username"] = json!(username);
+    }
+
+    if let Some(password) = password {
+        item["login"]["password"] = json!(password);
+    }
+
+    // Bitwarden expects null rather than an empty string when an item has no notes
+    if let Some(notes) = notes {
+        item["notes"] = if notes.trim().is_empty() {
+            Value::Null
+        } else {
+            json!(notes)
+        };
+    }
+
+    let encoded_item = general_purpose::STANDARD.encode(item.to_string());
+
+    let edit_output = Command::new("bw")
+        .arg("edit")
+        .arg("item")
+        .arg(id)
+        .arg(encoded_item)
+        .output()
+        .map_err(bw_command_error)?;
+
+    if !edit_output.status.success() {
+        return Err(anyhow!(
+            "Failed to edit vault item: {}",
+            stderr_message(&edit_output)
+        ));
+    }
+
+    status_println!("Updated vault item {}", id);
+
+    Ok(())
+}
+
+/// Reads the Epicor credentials from a vault login item into the environment the Epicor commands
+/// read them from, so they don't have to be kept on disk. The item's username and password become
+/// the basic auth, and its "API Key" custom field the API key.
+pub fn load_epicor_credentials(item_name: &str) -> Result<()> {
+    // Reuse the cached vault session or start a new one
+    let _session = start_session()?;
+
+    let get_output = Command::new("bw")
+        .arg("get")
+        .arg("item")
+        .arg(item_name)
+        .output()
+        .map_err(bw_command_error)?;
+
+    if !get_output.status.success() {
+        if is_not_found(&get_output) {
+            return Err(anyhow!(
+                "No vault item found named {} (set by EPICOR_CREDENTIALS_ITEM)",
+                item_name
+            ));
+        }
+
+        return Err(anyhow!(
+            "Failed to get Epicor credentials from the vault: {}",
+            stderr_message(&get_output)
+        ));
+    }
+
+    let item: Value = serde_json::from_slice(&get_output.stdout)
+        .map_err(|_| anyhow!("Failed to parse vault item {}", item_name))?;
+
+    let username = item["login"]["username"].as_str();
+    let password = item["login"]["password"].as_str();
+
+    let (Some(username), Some(password)) = (username, password) else {
+        return Err(anyhow!(
+            "Vault item {} needs the Epicor username and password as its login",
+            item_name
+        ));
+    };
+
+    let api_key = item["fields"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .find(|field| {
+            field["name"]
+                .as_str()
+                .is_some_and(|name| name.eq_ignore_ascii_case("API Key"))
+        })
+        .and_then(|field| field["value"].as_str())
+        .ok_or(anyhow!(
+            "Vault item {} needs an \"API Key\" custom field with the Epicor API key",
+            item_name
+        ))?;
+
+    env::set_var("EPICOR_API_KEY", api_key);
+    env::set_var("EPICOR_BASIC_AUTH", generate_basic_auth(username, password));
+    Ok(())
+}
+
+/// Runs `bw generate` with the requested character sets or passphrase options.
+/// Generating doesn't need the vault, so no session is started.
+fn generate_password(options: &GenerateCommand) -> Result<String> {
+    let mut generate_command = Command::new("bw");
+    generate_command.arg("generate");
+
+    if options.passphrase {
+        generate_command.arg("--passphrase");
+
+        if let Some(words) = options.words {
+            generate_command.arg("--words").arg(words.to_string());
+        }
+
+        if let Some(separator) = &options.separator {
+            generate_command.arg("--separator").arg(separator);
+        }
+    } else {
+        let character_sets = [
+            (options.uppercase, "--uppercase"),
+            (options.lowercase, "--lowercase"),
+            (options.number, "--number"),
+            (options.special, "--special"),
+        ];
+
+        for (enabled, flag) in character_sets {
+            if enabled {
+                generate_command.arg(flag);
+            }
+        }
+
+        if let Some(length) = options.length {
+            generate_command.arg("--length").arg(length.to_string());
+        }
+    }
+
+    let generate_output = generate_command
+        .output()
+        .map_err(bw_command_error)?;
+
+    if !generate_output.status.success() {
+        return Err(anyhow!(
+            "Failed to generate password: {}",
+            stderr_message(&generate_output)
+        ));
     }
 
-    println!("{}", String::from_utf8(get_output.stdout).unwrap());
+    Ok(String::from_utf8_lossy(&generate_output.stdout).trim().to_string())
+}
+
+pub fn generate(options: &GenerateCommand) -> Result<()> {
+    let password = generate_password(options)?;
+
+    // Store the password straight away rather than printing it, when asked to
+    if let Some(name) = &options.create {
+        return create_item(name, options.username.as_deref().unwrap_or(""), &password, None);
+    }
+
+    data_println!("{}", password);
+
+    Ok(())
+}
+
+pub fn vault_status(json: bool) -> Result<()> {
+    // bw can only tell that the vault is unlocked when given the session, so use the cached one.
+    // This doesn't log in or unlock, since the point is to see the current state.
+    if let Some(session_key) = load_cached_session() {
+        env::set_var("BW_SESSION", session_key);
+    }
+
+    let status_output = Command::new("bw")
+        .arg("status")
+        .output()
+        .map_err(bw_command_error)?;
+
+    if !status_output.status.success() {
+        return Err(anyhow!(
+            "Failed to get vault status: {}",
+            stderr_message(&status_output)
+        ));
+    }
+
+    // In JSON mode pass bw's status through so it can be piped into other tools
+    if json {
+        let status_json: Value = serde_json::from_slice(&status_output.stdout)
+            .map_err(|_| anyhow!("Failed to parse vault status"))?;
+
+        data_println!("{}", to_json(&status_json)?);
+        return Ok(());
+    }
+
+    let vault_status: VaultStatus = serde_json::from_slice(&status_output.stdout)
+        .map_err(|_| anyhow!("Failed to parse vault status"))?;
+
+    let status = match vault_status.status.as_str() {
+        "unauthenticated" => "Logged out".bright_red().bold(),
+        "locked" => "Locked".yellow().bold(),
+        "unlocked" => "Unlocked".bright_green().bold(),
+        other => other.normal(),
+    };
 
-    // Lock vault
-    lock_vault()?;
+    let not_set = || "-".to_string();
 
-    // Logout of vault
-    logout()?;
+    print_fields(&[
+        ("Status", status.to_string()),
+        ("Account", vault_status.user_email.unwrap_or_else(not_set)),
+        ("Server", vault_status.server_url.unwrap_or_else(not_set)),
+        ("Last Sync", vault_status.last_sync.unwrap_or_else(not_set)),
+    ]);
 
     Ok(())
 }
@@ -0,0 +1,773 @@
+use crate::epicor::{api_version, check_api_version, check_company, company, normalize_base_url};
+use crate::output::{data_println, status_println, to_json};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+/// Service name Omni's secrets are stored under in the OS keychain
+const KEYRING_SERVICE: &str = "omni";
+
+/// Every setting Omni reads, in the order `config show` prints them
+pub const SETTINGS: [&str; 19] = [
+    "BW_SERVER",
+    "BW_CLIENTID",
+    "BW_CLIENTSECRET",
+    "MASTER_PASSWORD",
+    "EPICOR_BASE_URL",
+    "EPICOR_API_KEY",
+    "EPICOR_BASIC_AUTH",
+    "EPICOR_COMPANY",
+    "EPICOR_API_VERSION",
+    "EPICOR_DEFAULT_ASSIGNEE",
+    "EPICOR_CREDENTIALS_ITEM",
+    "EPICOR_CASE_URL_TEMPLATE",
+    "EPICOR_DEFAULT_PLANT",
+    "EPICOR_CA_CERT",
+    "EPICOR_TIMEOUT_SECS",
+    "EPICOR_MAX_RETRIES",
+    "OPENAI_API_KEY",
+    "OPENAI_MODEL",
+    "HTTPS_PROXY",
+];
+
+/// Settings whose values are kept in the OS keychain and never printed in full
+pub const SECRET_SETTINGS: [&str; 5] = [
+    "BW_CLIENTSECRET",
+    "MASTER_PASSWORD",
+    "EPICOR_API_KEY",
+    "EPICOR_BASIC_AUTH",
+    "OPENAI_API_KEY",
+];
+
+/// Profile loaded by --profile or default_profile, whose keychain entries secrets are read from
+static ACTIVE_PROFILE: OnceLock<String> = OnceLock::new();
+
+/// Set by the global --config flag, a config.toml or .env file used instead of the usual ones
+static CONFIG_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Contents of config.toml, which holds named profiles for different environments
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Config {
+    /// Profile used when --profile isn't given
+    pub default_profile: Option<String>,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+    /// Short aliases for assignees, e.g. jdoe = "John Doe"
+    #[serde(default)]
+    pub assignees: BTreeMap<String, String>,
+    /// Settings that differ per Epicor company, keyed by company ID, e.g. [company.100]
+    #[serde(default)]
+    pub company: BTreeMap<String, CompanyConfig>,
+}
+
+/// Reps and owners for one Epicor company
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct CompanyConfig {
+    /// Who `complete-task --to-me` assigns the next task to in this company
+    pub default_assignee: Option<String>,
+    /// Owner of cases created in this company when --owner isn't given
+    pub default_owner: Option<String>,
+    /// Assignee aliases for this company, taking precedence over the top-level [assignees]
+    #[serde(default)]
+    pub assignees: BTreeMap<String, String>,
+}
+
+/// Epicor and Bitwarden settings for one environment (e.g. prod or test)
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Profile {
+    /// Self-hosted Bitwarden or Vaultwarden server, when not using bitwarden.com
+    pub bw_server: Option<String>,
+    pub bw_client_id: Option<String>,
+    pub bw_client_secret: Option<String>,
+    pub master_password: Option<String>,
+    pub epicor_base_url: Option<String>,
+    pub epicor_api_key: Option<String>,
+    pub epicor_basic_auth: Option<String>,
+    pub openai_api_key: Option<String>,
+    /// Proxy for all outbound requests, e.g. http://proxy.example.com:8080
+    pub https_proxy: Option<String>,
+    /// Your own sales rep name, which `complete-task --to-me` assigns the next task to
+    pub epicor_default_assignee: Option<String>,
+    /// Bitwarden login item to read the Epicor API key and basic auth from, e.g. "Epicor Prod"
+    pub epicor_credentials_item: Option<String>,
+    /// Link to a case in the Epicor web client, with {case} and {company} placeholders
+    pub epicor_case_url_template: Option<String>,
+    /// Plant time entries are booked in when --plant isn't given
+    pub epicor_default_plant: Option<String>,
+    /// Epicor company ID, e.g. 100
+    pub epicor_company: Option<String>,
+    /// Epicor REST API version, e.g. v2
+    pub epicor_api_version: Option<String>,
+    /// PEM certificate to trust for an Epicor server with a self-signed certificate
+    pub epicor_ca_cert: Option<String>,
+    /// Seconds to wait for Epicor before a request times out
+    pub epicor_timeout_secs: Option<String>,
+    /// How many times a failed Epicor request is retried
+    pub epicor_max_retries: Option<String>,
+    /// OpenAI model that summarizes case comments
+    pub openai_model: Option<String>,
+}
+
+impl Profile {
+    /// Pairs each setting with the environment variable it's read from
+    pub fn env_vars(&self) -> [(&'static str, &Option<String>); 19] {
+        [
+            ("BW_SERVER", &self.bw_server),
+            ("BW_CLIENTID", &self.bw_client_id),
+            ("BW_CLIENTSECRET", &self.bw_client_secret),
+            ("MASTER_PASSWORD", &self.master_password),
+            ("EPICOR_BASE_URL", &self.epicor_base_url),
+            ("EPICOR_API_KEY", &self.epicor_api_key),
+            ("EPICOR_BASIC_AUTH", &self.epicor_basic_auth),
+            ("OPENAI_API_KEY", &self.openai_api_key),
+            ("HTTPS_PROXY", &self.https_proxy),
+            ("EPICOR_DEFAULT_ASSIGNEE", &self.epicor_default_assignee),
+            ("EPICOR_CREDENTIALS_ITEM", &self.epicor_credentials_item),
+            ("EPICOR_CASE_URL_TEMPLATE", &self.epicor_case_url_template),
+            ("EPICOR_DEFAULT_PLANT", &self.epicor_default_plant),
+            ("EPICOR_COMPANY", &self.epicor_company),
+            ("EPICOR_API_VERSION", &self.epicor_api_version),
+            ("EPICOR_CA_CERT", &self.epicor_ca_cert),
+            ("EPICOR_TIMEOUT_SECS", &self.epicor_timeout_secs),
+            ("EPICOR_MAX_RETRIES", &self.epicor_max_retries),
+            ("OPENAI_MODEL", &self.openai_model),
+        ]
+    }
+
+    /// Sets the profile's settings as environment variables, which is where the commands read them from
+    fn apply(&self) {
+        for (name, value) in self.env_vars() {
+            if let Some(value) = value {
+                env::set_var(name, value);
+            }
+        }
+    }
+
+    /// Moves the secrets into the OS keychain so that only non-secret settings are written to disk.
+    /// A secret that can't be stored (e.g. there's no keychain on a headless machine) is left in place.
+    pub fn move_secrets_to_keyring(&mut self, profile: Option<&str>) {
+        let secrets = [
+            ("BW_CLIENTSECRET", &mut self.bw_client_secret),
+            ("MASTER_PASSWORD", &mut self.master_password),
+            ("EPICOR_API_KEY", &mut self.epicor_api_key),
+            ("EPICOR_BASIC_AUTH", &mut self.epicor_basic_auth),
+            ("OPENAI_API_KEY", &mut self.openai_api_key),
+        ];
+
+        for (name, value) in secrets {
+            let Some(secret) = value.as_deref() else {
+                continue;
+            };
+
+            match store_secret(profile, name, secret) {
+                Ok(()) => *value = None,
+                Err(e) => eprintln!(
+                    "Warning: could not store {} in the OS keychain ({}). It will be saved to the config file instead.",
+                    name, e
+                ),
+            }
+        }
+    }
+}
+
+impl Config {
+    /// Returns the settings for the selected company (--company or EPICOR_COMPANY). Once any
+    /// [company.*] sections exist, the selected company must have one too.
+    fn company_config(&self) -> Result<Option<&CompanyConfig>> {
+        if self.company.is_empty() {
+            return Ok(None);
+        }
+
+        let company = company()?;
+
+        match self.company.get(&company) {
+            Some(company_config) => Ok(Some(company_config)),
+            None => Err(anyhow!(
+                "No [company.{}] section in {}",
+                company,
+                config_file_path()?.display()
+            )),
+        }
+    }
+
+    pub fn load() -> Result<Self> {
+        let config_file_path = config_file_path()?;
+
+        if !config_file_path.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = fs::read_to_string(&config_file_path)?;
+
+        toml::from_str(&contents)
+            .map_err(|e| anyhow!("Failed to parse {}: {}", config_file_path.display(), e))
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let config_file_path = config_file_path()?;
+
+        // Make sure the config directory exists before writing to it
+        if let Some(parent) = config_file_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        // The profiles hold secrets
+        write_private_file(&config_file_path, &toml::to_string_pretty(self)?)
+    }
+}
+
+/// Returns Omni's config directory (e.g. ~/.config/omni on Linux)
+pub fn config_dir() -> Result<PathBuf> {
+    let config_dir = dirs::config_dir().ok_or(anyhow!("Failed to find config directory"))?;
+
+    Ok(config_dir.join("omni"))
+}
+
+/// Returns Omni's cache directory (e.g. ~/.cache/omni on Linux), or OMNI_CACHE_DIR if it's set
+pub fn cache_dir() -> Option<PathBuf> {
+    if let Some(cache_dir) = env::var_os("OMNI_CACHE_DIR") {
+        return Some(PathBuf::from(cache_dir));
+    }
+
+    Some(dirs::cache_dir()?.join("omni"))
+}
+
+fn is_toml_file(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension == "toml")
+}
+
+/// Returns the path of the config.toml file holding the named profiles
+pub fn config_file_path() -> Result<PathBuf> {
+    if let Some(path) = CONFIG_OVERRIDE.get().filter(|path| is_toml_file(path)) {
+        return Ok(path.clone());
+    }
+
+    Ok(config_dir()?.join("config.toml"))
+}
+
+/// Returns the path of the .env file written by `omni setup`
+pub fn env_file_path() -> Result<PathBuf> {
+    if let Some(path) = CONFIG_OVERRIDE.get().filter(|path| !is_toml_file(path)) {
+        return Ok(path.clone());
+    }
+
+    Ok(config_dir()?.join(".env"))
+}
+
+/// Uses the given file in place of config.toml (for a .toml file) or the .env file (otherwise)
+pub fn set_config_override(path: &Path) {
+    let _ = CONFIG_OVERRIDE.set(path.to_path_buf());
+}
+
+/// Loads the file given with --config instead of discovering the config files.
+/// A missing file is an error, rather than carrying on with nothing configured.
+pub fn load_config_override(path: &Path, profile: Option<&str>) -> Result<()> {
+    if !path.is_file() {
+        return Err(anyhow!("Config file {} does not exist", path.display()));
+    }
+
+    if !is_toml_file(path) {
+        dotenv::from_path(path).map_err(|e| anyhow!("Failed to load {}: {}", path.display(), e))?;
+        return Ok(());
+    }
+
+    if load_profile(profile)?.is_none() {
+        return Err(anyhow!(
+            "{} has no default_profile. Pass --profile to pick one.",
+            path.display()
+        ));
+    }
+
+    Ok(())
+}
+
+/// Loads the .env file, preferring Omni's config directory and falling back to the
+/// current directory for env files written by older versions of Omni.
+/// Returns the path of the file that was loaded, if any.
+pub fn load_env_file() -> Option<PathBuf> {
+    if let Ok(env_file_path) = env_file_path() {
+        if env_file_path.exists() && dotenv::from_path(&env_file_path).is_ok() {
+            return Some(env_file_path);
+        }
+    }
+
+    dotenv::dotenv().ok()
+}
+
+/// Explains where Omni looks for its .env file, for when none could be found
+pub fn env_file_not_found_message() -> String {
+    let config_env_file = env_file_path()
+        .map(|path| path.display().to_string())
+        .unwrap_or("<config dir>/omni/.env".to_string());
+
+    format!(
+        "No .env file found. Omni looks for {} first, then .env in the current directory. Run `omni setup` to create one.",
+        config_env_file
+    )
+}
+
+fn keyring_entry(profile: Option<&str>, name: &str) -> Result<keyring::Entry> {
+    // Each profile gets its own entries so that e.g. prod and test credentials don't clash
+    let user = match profile {
+        Some(profile) => format!("{}/{}", profile, name),
+        None => name.to_string(),
+    };
+
+    Ok(keyring::Entry::new(KEYRING_SERVICE, &user)?)
+}
+
+/// Stores a secret in the OS keychain, under the given profile if there is one
+pub fn store_secret(profile: Option<&str>, name: &str, value: &str) -> Result<()> {
+    keyring_entry(profile, name)?.set_password(value)?;
+
+    Ok(())
+}
+
+/// Reads a secret such as EPICOR_API_KEY. The environment is checked first so that flags,
+/// exported variables and older .env files still work, then the OS keychain.
+pub fn secret(name: &str) -> Option<String> {
+    if let Ok(value) = env::var(name) {
+        return Some(value);
+    }
+
+    keyring_entry(active_profile(), name).ok()?.get_password().ok()
+}
+
+/// Returns the name of the profile loaded by --profile or default_profile, if any
+pub fn active_profile() -> Option<&'static str> {
+    ACTIVE_PROFILE.get().map(|profile| profile.as_str())
+}
+
+/// Explains that a required setting is missing, and where it's expected to come from
+fn missing_setting_error(name: &str) -> anyhow::Error {
+    missing_settings_error(&[name])
+}
+
+/// Explains that one or more required settings are missing, and where they're expected to come
+/// from, so they can all be fixed at once
+pub fn missing_settings_error(names: &[&str]) -> anyhow::Error {
+    let location = match active_profile() {
+        Some(profile) => config_file_path()
+            .map(|path| format!("the [profiles.{}] section of {}", profile, path.display())),
+        None => env_file_path().map(|path| path.display().to_string()),
+    }
+    .unwrap_or("your .env file".to_string());
+
+    let not_set = match names {
+        [name] => format!("{} is not set. Run `omni setup` or add it", name),
+        _ => format!("{} are not set. Run `omni setup` or add them", names.join(", ")),
+    };
+
+    anyhow!("{} to {}", not_set, location)
+}
+
+/// Reads a required setting such as EPICOR_BASE_URL from the environment
+pub fn required_env(name: &str) -> Result<String> {
+    env::var(name).map_err(|_| missing_setting_error(name))
+}
+
+/// Reads a required secret such as EPICOR_API_KEY from the environment or the OS keychain
+pub fn required_secret(name: &str) -> Result<String> {
+    secret(name).ok_or_else(|| missing_setting_error(name))
+}
+
+/// Loads the profile named by --profile, or the default profile, into the environment.
+/// Returns the name of the profile that was loaded, if any.
+pub fn load_profile(profile: Option<&str>) -> Result<Option<String>> {
+    let config = Config::load()?;
+
+    let Some(name) = profile
+        .map(|profile| profile.to_string())
+        .or(config.default_profile.clone())
+    else {
+        return Ok(None);
+    };
+
+    let profile = config.profiles.get(&name).ok_or(anyhow!(
+        "Profile '{}' not found in {}. Run `omni setup --profile {}` to create it.",
+        name,
+        config_file_path()?.display(),
+        name
+    ))?;
+
+    profile.apply();
+
+    let _ = ACTIVE_PROFILE.set(name.clone());
+
+    Ok(Some(name))
+}
+
+/// Expands an assignee alias from the [assignees] table in config.toml to the full sales rep name.
+/// Names without an alias are returned unchanged.
+pub fn resolve_assignee(name: &str) -> Result<String> {
+    let config = Config::load()?;
+
+    let expand = |assignees: &BTreeMap<String, String>| {
+        assignees
+            .iter()
+            .find(|(alias, _)| alias.eq_ignore_ascii_case(name))
+            .map(|(_, full_name)| full_name.clone())
+    };
+
+    // The selected company's aliases take precedence over the shared ones. A company without a
+    // [company.*] section just has no aliases of its own, so full names still pass through.
+    let company_full_name = if config.company.is_empty() {
+        None
+    } else {
+        config
+            .company
+            .get(&company()?)
+            .and_then(|company| expand(&company.assignees))
+    };
+
+    let full_name = company_full_name
+        .or_else(|| expand(&config.assignees))
+        .unwrap_or(name.to_string());
+
+    Ok(full_name)
+}
+
+/// Returns the assignee `--to-me` stands for, set with default_assignee in the company's
+/// [company.*] section, EPICOR_DEFAULT_ASSIGNEE or epicor_default_assignee in the profile
+pub fn default_assignee() -> Result<String> {
+    let config = Config::load()?;
+
+    if let Some(default_assignee) = config
+        .company_config()?
+        .and_then(|company| company.default_assignee.clone())
+    {
+        return Ok(default_assignee);
+    }
+
+    env::var("EPICOR_DEFAULT_ASSIGNEE")
+        .ok()
+        .filter(|assignee| !assignee.trim().is_empty())
+        .ok_or(anyhow!(
+            "--to-me needs your sales rep name in EPICOR_DEFAULT_ASSIGNEE (or epicor_default_assignee in your profile). Otherwise pass --assign-to."
+        ))
+}
+
+/// Returns the plant time entries are booked in when --plant isn't given, set with
+/// EPICOR_DEFAULT_PLANT or epicor_default_plant in the profile
+pub fn default_plant() -> Option<String> {
+    env::var("EPICOR_DEFAULT_PLANT")
+        .ok()
+        .filter(|plant| !plant.trim().is_empty())
+}
+
+/// Returns the owner for new cases from the company's [company.*] section, if there is one
+pub fn default_owner() -> Result<Option<String>> {
+    let config = Config::load()?;
+
+    Ok(config.company_config()?.and_then(|company| company.default_owner.clone()))
+}
+
+/// Writes a profile into config.toml, making it the default if there's no default yet
+pub fn save_profile(name: &str, profile: Profile) -> Result<()> {
+    let mut config = Config::load()?;
+
+    config.profiles.insert(name.to_string(), profile);
+
+    if config.default_profile.is_none() {
+        config.default_profile = Some(name.to_string());
+    }
+
+    config.save()
+}
+
+/// How `config show` prints the settings
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ConfigFormat {
+    Env,
+    Json,
+    Toml,
+}
+
+impl FromStr for ConfigFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "env" => Ok(ConfigFormat::Env),
+            "json" => Ok(ConfigFormat::Json),
+            "toml" => Ok(ConfigFormat::Toml),
+            _ => Err(format!("{} is not a valid format (env, json or toml)", s)),
+        }
+    }
+}
+
+/// Shows the last 4 characters of a secret, so it can be told apart from others without being
+/// exposed. Short secrets are hidden completely.
+fn mask_secret(value: &str) -> String {
+    let chars: Vec<char> = value.chars().collect();
+
+    if chars.len() < 8 {
+        return "****".to_string();
+    }
+
+    format!("****{}", chars[chars.len() - 4..].iter().collect::<String>())
+}
+
+/// The value a setting resolves to once the profile, .env file and flags have been applied
+fn resolved_setting(name: &str) -> Option<String> {
+    if SECRET_SETTINGS.contains(&name) {
+        return secret(name).map(|value| mask_secret(&value));
+    }
+
+    match name {
+        // These have defaults, which are what the Epicor commands use when they're unset
+        "EPICOR_COMPANY" => env::var(name).ok().or(company().ok()),
+        "EPICOR_API_VERSION" => env::var(name).ok().or(api_version().ok()),
+        _ => env::var(name).ok(),
+    }
+}
+
+/// Prints the effective settings, with secrets masked, to check which profile, file or flag
+/// each one came from. Unset settings are left out.
+pub fn show_config(format: ConfigFormat) -> Result<()> {
+    match active_profile() {
+        Some(profile) => status_println!("Settings from profile '{}'", profile),
+        None => status_println!("Settings from the .env file and environment"),
+    }
+
+    let settings: BTreeMap<&str, String> = SETTINGS
+        .iter()
+        .filter_map(|name| Some((*name, resolved_setting(name)?)))
+        .collect();
+
+    match format {
+        ConfigFormat::Env => {
+            // Keep the original order, which groups related settings
+            for name in SETTINGS {
+                if let Some(value) = settings.get(name) {
+                    data_println!("{}", env_line(name, value));
+                }
+            }
+        }
+        ConfigFormat::Json => data_println!("{}", to_json(&settings)?),
+        ConfigFormat::Toml => data_println!("{}", toml::to_string(&settings)?.trim_end()),
+    }
+
+    Ok(())
+}
+
+/// Formats a setting as a line of a .env file
+pub fn env_line(name: &str, value: &str) -> String {
+    let is_plain = |c: char| c.is_ascii_alphanumeric() || "-_./:@,+".contains(c);
+
+    if value.chars().all(is_plain) {
+        return format!("{}={}", name, value);
+    }
+
+    // Single quotes keep spaces (e.g. "Basic <credentials>"), $ and backslashes as they are.
+    // A ' can't be quoted, so it closes the quotes, is escaped and reopens them as in a shell.
+    format!("{}='{}'", name, value.replace('\'', r"'\''"))
+}
+
+/// The key a setting is stored under in a config.toml profile
+fn profile_key(name: &str) -> Option<&'static str> {
+    let key = match name {
+        "BW_SERVER" => "bw_server",
+        "BW_CLIENTID" => "bw_client_id",
+        "BW_CLIENTSECRET" => "bw_client_secret",
+        "MASTER_PASSWORD" => "master_password",
+        "EPICOR_BASE_URL" => "epicor_base_url",
+        "EPICOR_API_KEY" => "epicor_api_key",
+        "EPICOR_BASIC_AUTH" => "epicor_basic_auth",
+        "OPENAI_API_KEY" => "openai_api_key",
+        "HTTPS_PROXY" => "https_proxy",
+        "EPICOR_DEFAULT_ASSIGNEE" => "epicor_default_assignee",
+        "EPICOR_CREDENTIALS_ITEM" => "epicor_credentials_item",
+        "EPICOR_CASE_URL_TEMPLATE" => "epicor_case_url_template",
+        "EPICOR_DEFAULT_PLANT" => "epicor_default_plant",
+        "EPICOR_COMPANY" => "epicor_company",
+        "EPICOR_API_VERSION" => "epicor_api_version",
+        "EPICOR_CA_CERT" => "epicor_ca_cert",
+        "EPICOR_TIMEOUT_SECS" => "epicor_timeout_secs",
+        "EPICOR_MAX_RETRIES" => "epicor_max_retries",
+        "OPENAI_MODEL" => "openai_model",
+        _ => return None,
+    };
+
+    Some(key)
+}
+
+/// Updates one setting in the active profile, or in the .env file when no profile is in use,
+/// leaving the other settings and comments as they are. Secrets go to the OS keychain like
+/// setup puts them, and any copy of them in the file is removed so it doesn't take precedence.
+pub fn set_config_value(name: &str, value: &str) -> Result<()> {
+    let name = name.to_uppercase();
+
+    if !SETTINGS.contains(&name.as_str()) {
+        return Err(anyhow!(
+            "Unknown setting {}. Known settings are: {}",
+            name,
+            SETTINGS.join(", ")
+        ));
+    }
+
+    // Check the value the way the commands reading it will, so a typo is caught now
+    let value = match name.as_str() {
+        "EPICOR_BASE_URL" => normalize_base_url(value)?,
+        "EPICOR_COMPANY" => {
+            check_company(value)?;
+            value.to_string()
+        }
+        "EPICOR_API_VERSION" => {
+            check_api_version(value)?;
+            value.to_string()
+        }
+        "EPICOR_TIMEOUT_SECS" | "EPICOR_MAX_RETRIES" => {
+            value
+                .trim()
+                .parse::<u32>()
+                .map_err(|_| anyhow!("{} must be a whole number, e.g. 30", name))?
+                .to_string()
+        }
+        _ => value.to_string(),
+    };
+
+    let profile = active_profile();
+
+    let in_keychain = SECRET_SETTINGS.contains(&name.as_str())
+        && match store_secret(profile, &name, &value) {
+            Ok(()) => true,
+            Err(e) => {
+                eprintln!(
+                    "Warning: could not store {} in the OS keychain ({}). It will be saved to the config file instead.",
+                    name, e
+                );
+                false
+            }
+        };
+
+    let file_value = if in_keychain { None } else { Some(value.as_str()) };
+
+    let path = match profile {
+        Some(profile) => {
+            let key = profile_key(&name).ok_or(anyhow!(
+                "{} can't be set in a profile. Set it in the environment instead.",
+                name
+            ))?;
+
+            set_profile_value(profile, key, file_value)?
+        }
+        None => set_env_file_value(&name, file_value)?,
+    };
+
+    if in_keychain {
+        status_println!("Stored {} in the OS keychain", name);
+    } else {
+        status_println!("Set {} in {}", name, path.display());
+    }
+
+    Ok(())
+}
+
+/// Sets (or with None, removes) a key of a profile in config.toml. Returns the file's path.
+fn set_profile_value(profile: &str, key: &str, value: Option<&str>) -> Result<PathBuf> {
+    let path = config_file_path()?;
+
+    let contents = fs::read_to_string(&path)
+        .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+
+    // toml_edit keeps the comments and layout that a round trip through Config would lose
+    let mut document: toml_edit::Document = contents
+        .parse()
+        .map_err(|e| anyhow!("Failed to parse {}: {}", path.display(), e))?;
+
+    let profile_table = document["profiles"][profile]
+        .as_table_like_mut()
+        .ok_or(anyhow!("Profile '{}' not found in {}", profile, path.display()))?;
+
+    match value {
+        Some(value) => {
+            profile_table.insert(key, toml_edit::value(value));
+        }
+        None => {
+            profile_table.remove(key);
+        }
+    }
+
+    write_private_file(&path, &document.to_string())?;
+
+    Ok(path)
+}
+
+/// Sets (or with None, removes) a setting in the .env file. Returns the file's path.
+fn set_env_file_value(name: &str, value: Option<&str>) -> Result<PathBuf> {
+    let path = env_file_path()?;
+
+    let contents = fs::read_to_string(&path).map_err(|_| {
+        anyhow!("No .env file found at {}. Run `omni setup` to create one.", path.display())
+    })?;
+
+    let is_setting = |line: &str| {
+        let line = line.trim_start();
+        let line = line.strip_prefix("export ").unwrap_or(line);
+
+        line.split_once('=')
+            .is_some_and(|(line_name, _)| line_name.trim() == name)
+    };
+
+    let mut lines = Vec::new();
+    let mut replaced = false;
+
+    for line in contents.lines() {
+        if !is_setting(line) {
+            lines.push(line.to_string());
+            continue;
+        }
+
+        // The first definition is the one that's loaded, later duplicates are dropped
+        if !replaced {
+            if let Some(value) = value {
+                lines.push(env_line(name, value));
+            }
+
+            replaced = true;
+        }
+    }
+
+    if !replaced {
+        if let Some(value) = value {
+            lines.push(env_line(name, value));
+        }
+    }
+
+    write_private_file(&path, &format!("{}\n", lines.join("\n")))?;
+
+    Ok(path)
+}
+
+/// Overwrites a file that holds secrets so that only the current user can read it. The
+/// permissions are set before anything is written, so the secrets are never readable by others.
+pub(crate) fn write_private_file(path: &Path, contents: &str) -> Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let mut file = options.open(path)?;
+
+    // The mode only applies to a new file, so also tighten one that already existed
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    }
+
+    file.write_all(contents.as_bytes())?;
+
+    Ok(())
+}
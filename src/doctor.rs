@@ -0,0 +1,152 @@
+use crate::config::{active_profile, config_file_path, env_file_path, secret};
+use crate::epicor::{api_version, ping, EpicorClient};
+use crate::output::data_println;
+use crate::setup::installed_bw;
+use anyhow::{anyhow, Result};
+use colored::Colorize;
+use std::env;
+use std::path::Path;
+
+/// Settings the Bitwarden and Epicor commands can't work without
+const REQUIRED_SETTINGS: [&str; 6] = [
+    "BW_CLIENTID",
+    "BW_CLIENTSECRET",
+    "MASTER_PASSWORD",
+    "EPICOR_BASE_URL",
+    "EPICOR_API_KEY",
+    "EPICOR_BASIC_AUTH",
+];
+
+/// Settings only some features use
+const OPTIONAL_SETTINGS: [&str; 9] = [
+    "OPENAI_API_KEY",
+    "BW_SERVER",
+    "HTTPS_PROXY",
+    "EPICOR_DEFAULT_ASSIGNEE",
+    "EPICOR_CASE_URL_TEMPLATE",
+    "EPICOR_DEFAULT_PLANT",
+    "EPICOR_CA_CERT",
+    "EPICOR_TIMEOUT_SECS",
+    "EPICOR_MAX_RETRIES",
+];
+
+/// Prints one line of the checklist. None marks an optional item that isn't set up.
+fn print_check(passed: Option<bool>, item: &str, detail: &str) {
+    let mark = match passed {
+        Some(true) => "✓".bright_green().bold(),
+        Some(false) => "✗".red().bold(),
+        None => "-".yellow().bold(),
+    };
+
+    data_println!("{} {:<24} {}", mark, item, detail);
+}
+
+/// Audits Omni's configuration: the Bitwarden CLI, the config files, which settings are present
+/// (never their values) and whether Epicor is reachable
+pub async fn doctor() -> Result<()> {
+    let mut failures = 0;
+
+    let mut check = |passed: Option<bool>, item: &str, detail: &str| {
+        if passed == Some(false) {
+            failures += 1;
+        }
+
+        print_check(passed, item, detail);
+    };
+
+    data_println!("Omni {}", env!("CARGO_PKG_VERSION"));
+
+    match installed_bw() {
+        Some((version, path)) => {
+            let location = path
+                .map(|path| path.display().to_string())
+                .unwrap_or("PATH".to_string());
+            check(Some(true), "Bitwarden CLI", &format!("{} at {}", version, location));
+        }
+        None => check(Some(false), "Bitwarden CLI", "bw not found on PATH (run `omni setup`)"),
+    }
+
+    let config_file = config_file_path()?;
+    let profile = active_profile();
+
+    match profile {
+        Some(profile) => check(
+            Some(true),
+            "Config file",
+            &format!("{} (profile {})", config_file.display(), profile),
+        ),
+        None if config_file.exists() => check(
+            None,
+            "Config file",
+            &format!("{} (no profile selected)", config_file.display()),
+        ),
+        None => check(None, "Config file", "not found (only needed for profiles)"),
+    }
+
+    // Commands read the .env file from the config directory, then the current directory
+    let env_file = env_file_path()?;
+    let local_env_file = Path::new(".env");
+
+    if env_file.exists() {
+        check(Some(true), ".env file", &env_file.display().to_string());
+    } else if local_env_file.exists() {
+        check(Some(true), ".env file", ".env in the current directory");
+    } else if profile.is_some() {
+        check(None, ".env file", "not found (settings come from the profile)");
+    } else {
+        check(Some(false), ".env file", "not found and no profile selected (run `omni setup`)");
+    }
+
+    // Secrets may come from the environment or the OS keychain, and are never printed
+    for name in REQUIRED_SETTINGS {
+        if secret(name).is_some() {
+            check(Some(true), name, "set");
+        } else {
+            check(Some(false), name, "not set");
+        }
+    }
+
+    for name in OPTIONAL_SETTINGS {
+        if secret(name).is_some() {
+            check(Some(true), name, "set");
+        } else {
+            check(None, name, "not set");
+        }
+    }
+
+    match api_version() {
+        Ok(api_version) => check(Some(true), "EPICOR_API_VERSION", &api_version),
+        Err(e) => check(Some(false), "EPICOR_API_VERSION", &e.to_string()),
+    }
+
+    if let Ok(ca_cert) = env::var("EPICOR_CA_CERT") {
+        if !Path::new(&ca_cert).is_file() {
+            check(Some(false), "EPICOR_CA_CERT", &format!("{} does not exist", ca_cert));
+        }
+    }
+
+    // Only try Epicor once there's something to connect with
+    if env::var("EPICOR_BASE_URL").is_ok() {
+        data_println!();
+
+        let ping_result = match EpicorClient::from_env() {
+            Ok(client) => ping(&client).await,
+            Err(e) => Err(e),
+        };
+
+        match ping_result {
+            Ok(()) => check(Some(true), "Epicor connection", "reachable"),
+            Err(e) => check(Some(false), "Epicor connection", &e.to_string()),
+        }
+    } else {
+        check(Some(false), "Epicor connection", "skipped, EPICOR_BASE_URL is not set");
+    }
+
+    if failures > 0 {
+        return Err(anyhow!("{} checks failed", failures));
+    }
+
+    println!("{}", "Everything looks good".bright_green().bold());
+
+    Ok(())
+}
@@ -1,11 +1,74 @@
-use anyhow::{anyhow, Result, Ok};
+use crate::args::parse_case_number;
+use crate::config::{cache_dir, missing_settings_error, secret};
+use crate::openai::summarize_comments;
+use crate::output::{data_println, print_fields, print_table, status_println, to_json, Spinner};
+use anyhow::{anyhow, Result};
+use base64::engine::general_purpose;
+use base64::Engine;
+use chrono::format::{Item, StrftimeItems};
+use chrono::NaiveDate;
+use clap::ValueEnum;
 use colored::Colorize;
+use dialoguer::Confirm;
+use futures::stream::{self, StreamExt};
+use regex::Regex;
 use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
-use reqwest::{Client, Response};
-use serde::{Deserialize, Serialize};
+use reqwest::{Certificate, Client, ClientBuilder, Response, StatusCode, Url};
+use serde::de::{self, DeserializeOwned};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::env;
 use std::error::Error;
-use std::fmt::Debug;
+use std::fmt::{self, Debug, Display, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use tracing::{debug, trace};
+
+/// Shared HTTP client for all Epicor requests, so connections are reused across calls
+static HTTP_CLIENT: OnceLock<Client> = OnceLock::new();
+
+/// Set by the global --dry-run flag, prints requests instead of sending them
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+
+/// Set by the global --danger-accept-invalid-certs flag, skips TLS certificate checks
+static ACCEPT_INVALID_CERTS: AtomicBool = AtomicBool::new(false);
+
+/// Set by the global --cache-ttl flag, how many seconds a case's status is reused from the
+/// disk cache. 0 turns the cache off.
+static CACHE_TTL_SECS: AtomicU64 = AtomicU64::new(0);
+
+/// Set by the global --date-format flag, how Epicor dates are shown
+static DATE_FORMAT: OnceLock<String> = OnceLock::new();
+
+/// How Epicor dates are shown when --date-format isn't given
+const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// Epicor company used in request URLs when EPICOR_COMPANY isn't set
+const DEFAULT_COMPANY: &str = "100";
+
+/// Epicor REST version used in request URLs when EPICOR_API_VERSION isn't set
+const DEFAULT_API_VERSION: &str = "v2";
+
+/// How long to wait for Epicor to respond when EPICOR_TIMEOUT_SECS isn't set
+const DEFAULT_TIMEOUT_SECS: u64 = 30;
+
+/// How many times a transient failure is retried when EPICOR_MAX_RETRIES isn't set
+const DEFAULT_MAX_RETRIES: u32 = 3;
+
+/// Upper limit on EPICOR_MAX_RETRIES, past which a failing request would only keep the user waiting
+const MAX_RETRIES: u32 = 10;
+
+/// Delay before the first retry, doubled on every subsequent attempt
+const RETRY_BASE_DELAY_MS: u64 = 500;
+
+/// Longest the backoff waits between two attempts
+const MAX_RETRY_DELAY_MS: u64 = 30_000;
+
+/// Largest file add-attachment will send, since the whole file goes into a single request
+const MAX_ATTACHMENT_BYTES: u64 = 10 * 1024 * 1024;
 
 pub struct TimeEntry {
     employee_id: u32,
@@ -13,15 +76,81 @@ pub struct TimeEntry {
     project_id: Option<String>,
     wbs_phase_id: Option<String>,
     operation: Option<u32>,
+    plant_id: Option<String>,
     expense_code: Option<ExpenseCode>,
+    hours: f32,
+}
+
+impl TimeEntry {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        employee_id: u32,
+        labor_type: LaborType,
+        project_id: Option<String>,
+        wbs_phase_id: Option<String>,
+        operation: Option<u32>,
+        plant_id: Option<String>,
+        expense_code: Option<ExpenseCode>,
+        hours: f32,
+    ) -> Self {
+        Self {
+            employee_id,
+            labor_type,
+            project_id,
+            wbs_phase_id,
+            operation,
+            plant_id,
+            expense_code,
+            hours,
+        }
+    }
+}
+
+/// Reads a list that Epicor may send as null when there's nothing in it
+fn null_as_empty<'de, D: Deserializer<'de>, T: Deserialize<'de>>(
+    deserializer: D,
+) -> Result<Vec<T>, D::Error> {
+    Ok(Option::<Vec<T>>::deserialize(deserializer)?.unwrap_or_default())
 }
 
-enum ExpenseCode {
+/// Epicor's expense code for labor, sent as its number
+#[derive(Debug, PartialEq, Clone, Copy, ValueEnum)]
+pub enum ExpenseCode {
     DirectLabor = 1,
-    IndirectLabor,
+    IndirectLabor = 2,
+}
+
+impl Serialize for ExpenseCode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u32(*self as u32)
+    }
+}
+
+impl<'de> Deserialize<'de> for ExpenseCode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match u32::deserialize(deserializer)? {
+            1 => Ok(ExpenseCode::DirectLabor),
+            2 => Ok(ExpenseCode::IndirectLabor),
+            code => Err(de::Error::custom(format!("{} is not a valid ExpenseCode", code))),
+        }
+    }
+}
+
+impl FromStr for ExpenseCode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "direct-labor" | "1" => Ok(ExpenseCode::DirectLabor),
+            "indirect-labor" | "2" => Ok(ExpenseCode::IndirectLabor),
+            _ => Err(format!("{} is not a valid ExpenseCode", s)),
+        }
+    }
 }
 
-enum LaborType {
+/// Epicor's labor type, sent by name (e.g. "Project")
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize, ValueEnum)]
+pub enum LaborType {
     Indirect,
     Project,
     Production,
@@ -29,34 +158,197 @@ enum LaborType {
     Setup,
 }
 
-impl LaborType {
-    fn as_str(&self) -> &str {
-        match self {
-            LaborType::Indirect => "Indirect",
-            LaborType::Project => "Project",
-            LaborType::Production => "Production",
-            LaborType::Service => "Service",
-            LaborType::Setup => "Setup",
+impl FromStr for LaborType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "indirect" => Ok(LaborType::Indirect),
+            "project" => Ok(LaborType::Project),
+            "production" => Ok(LaborType::Production),
+            "service" => Ok(LaborType::Service),
+            "setup" => Ok(LaborType::Setup),
+            _ => Err(format!("{} is not a valid LaborType", s)),
+        }
+    }
+}
+
+/// How `case list` prints its results
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ListFormat {
+    Table,
+    Json,
+    Csv,
+}
+
+impl FromStr for ListFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "table" => Ok(ListFormat::Table),
+            "json" => Ok(ListFormat::Json),
+            "csv" => Ok(ListFormat::Csv),
+            _ => Err(format!("{} is not a valid format (table, json or csv)", s)),
+        }
+    }
+}
+
+/// Error payload Epicor sends with most unsuccessful responses
+#[derive(Deserialize, Debug)]
+struct EpicorError {
+    #[serde(rename = "ErrorMessage")]
+    error_message: Option<String>,
+    #[serde(rename = "ErrorDetails")]
+    error_details: Option<Vec<EpicorErrorDetail>>,
+}
+
+#[derive(Deserialize, Debug)]
+struct EpicorErrorDetail {
+    #[serde(rename = "Message")]
+    message: Option<String>,
+}
+
+impl EpicorError {
+    /// The error message followed by any details that add to it
+    fn message(&self) -> Option<String> {
+        let mut messages: Vec<&str> = Vec::new();
+
+        let details = self
+            .error_details
+            .iter()
+            .flatten()
+            .filter_map(|detail| detail.message.as_deref());
+
+        for message in self.error_message.as_deref().into_iter().chain(details) {
+            let message = message.trim();
+
+            if !message.is_empty() && !messages.contains(&message) {
+                messages.push(message);
+            }
+        }
+
+        if messages.is_empty() {
+            return None;
+        }
+
+        Some(messages.join("; "))
+    }
+}
+
+/// Every Omni function reports failures through an `Error` flag and a `Message`
+trait EpicorResponse {
+    /// Returns the error message if Epicor reported the call as failed
+    fn error_message(&self) -> Option<String>;
+}
+
+/// Implements EpicorResponse from a response's `Error` flag and `Message`. A response with
+/// failures its command explains better (e.g. a case with an open task) passes
+/// `reported_by_caller` to leave those to the command.
+macro_rules! impl_epicor_response {
+    ($response:ty) => {
+        impl_epicor_response!($response, reported_by_caller: |_response| false);
+    };
+    ($response:ty, reported_by_caller: |$this:ident| $reported_by_caller:expr) => {
+        impl EpicorResponse for $response {
+            fn error_message(&self) -> Option<String> {
+                let $this = self;
+
+                if !self.error || $reported_by_caller {
+                    return None;
+                }
+
+                let message = self
+                    .message
+                    .as_deref()
+                    .map(|message| message.trim())
+                    .filter(|message| !message.is_empty())
+                    .unwrap_or("Unknown Error");
+
+                Some(message.to_string())
+            }
+        }
+    };
+}
+
+#[derive(Serialize, Debug)]
+pub struct AddTimeEntryInput {
+    #[serde(rename = "EmployeeNum")]
+    employee_num: u32,
+    #[serde(rename = "LaborType")]
+    labor_type: LaborType,
+    #[serde(rename = "ProjectID")]
+    project_id: Option<String>,
+    #[serde(rename = "WBSPhaseID")]
+    wbs_phase_id: Option<String>,
+    #[serde(rename = "OprSeq")]
+    operation: Option<u32>,
+    #[serde(rename = "Plant")]
+    plant_id: Option<String>,
+    #[serde(rename = "ExpenseCode")]
+    expense_code: Option<ExpenseCode>,
+    #[serde(rename = "LaborHrs")]
+    hours: f32,
+}
+
+impl From<TimeEntry> for AddTimeEntryInput {
+    fn from(entry: TimeEntry) -> Self {
+        Self {
+            employee_num: entry.employee_id,
+            labor_type: entry.labor_type,
+            project_id: entry.project_id,
+            wbs_phase_id: entry.wbs_phase_id,
+            operation: entry.operation,
+            plant_id: entry.plant_id,
+            expense_code: entry.expense_code,
+            hours: entry.hours,
         }
     }
 }
 
-pub enum RequestBodyType {
-    UpdateQuoteBody(UpdateQuoteInput),
-    CompleteTaskBody(CompleteTaskInput),
-    CaseStatusBody(CaseStatusInput),
-    AddCaseCommentBody(AddCaseCommentInput),
-    GetLastCommentBody(GetLastCommentInput),
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AddTimeEntryResponse {
+    #[serde(rename = "Error")]
+    error: bool,
+    #[serde(rename = "Message")]
+    message: Option<String>,
+}
+
+impl_epicor_response!(AddTimeEntryResponse);
+
+#[derive(Serialize, Debug)]
+pub struct ListTimeEntriesInput {
+    #[serde(rename = "EmployeeNum")]
+    employee_num: u32,
+    /// Day to list, as yyyy-mm-dd
+    #[serde(rename = "Date")]
+    date: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ListTimeEntriesResponse {
+    #[serde(rename = "Error")]
+    error: bool,
+    #[serde(rename = "Message")]
+    message: Option<String>,
+    #[serde(rename = "TimeEntries", default)]
+    time_entries: Vec<TimeEntrySummary>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(untagged)]
-pub enum ApiResponse {
-    UpdateQuoteBody(UpdateQuoteResponse),
-    CompleteTaskBody(CompleteTaskResponse),
-    CaseStatusBody(CaseStatusResponse),
-    AddCaseCommentBody(AddCaseCommentResponse),
-    GetLastCommentBody(GetLastCommentResponse),
+impl_epicor_response!(ListTimeEntriesResponse);
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TimeEntrySummary {
+    #[serde(rename = "ProjectID")]
+    project_id: Option<String>,
+    #[serde(rename = "WBSPhaseID")]
+    wbs_phase_id: Option<String>,
+    #[serde(rename = "OprSeq")]
+    operation: Option<u32>,
+    #[serde(rename = "LaborType")]
+    labor_type: String,
+    #[serde(rename = "LaborHrs")]
+    hours: f64,
 }
 
 #[derive(Serialize, Debug)]
@@ -75,6 +367,36 @@ pub struct GetLastCommentResponse {
     comment: Option<String>,
 }
 
+impl_epicor_response!(GetLastCommentResponse);
+
+#[derive(Serialize, Debug)]
+pub struct GetCommentsInput {
+    #[serde(rename = "CaseNum")]
+    case_num: u32,
+}
+
+/// A case without comments may come back with Comments left out or null
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetCommentsResponse {
+    #[serde(rename = "Error")]
+    error: bool,
+    #[serde(rename = "Message")]
+    message: Option<String>,
+    #[serde(rename = "Comments", default, deserialize_with = "null_as_empty")]
+    comments: Vec<CaseComment>,
+}
+
+impl_epicor_response!(GetCommentsResponse);
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CaseComment {
+    #[serde(rename = "Author")]
+    author: String,
+    #[serde(rename = "CreatedOn")]
+    created_on: String,
+    #[serde(rename = "Comment")]
+    comment: String,
+}
 
 #[derive(Serialize, Debug)]
 pub struct AddCaseCommentInput {
@@ -101,6 +423,63 @@ pub struct AddCaseCommentResponse {
     message: Option<String>,
 }
 
+impl_epicor_response!(AddCaseCommentResponse);
+
+#[derive(Serialize, Debug)]
+pub struct EditLastCommentInput {
+    #[serde(rename = "CaseNum")]
+    case_num: u32,
+    #[serde(rename = "Comment")]
+    comment: String,
+}
+
+impl EditLastCommentInput {
+    pub fn new(case_num: u32, comment: &str) -> Self {
+        Self {
+            case_num,
+            comment: comment.to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EditLastCommentResponse {
+    #[serde(rename = "Error")]
+    error: bool,
+    #[serde(rename = "Message")]
+    message: Option<String>,
+    #[serde(rename = "NoComments", default)]
+    no_comments: bool,
+}
+
+// A case without comments is reported by edit_last_comment, which knows the case number
+impl_epicor_response!(EditLastCommentResponse, reported_by_caller: |response| response.no_comments);
+
+#[derive(Serialize, Debug)]
+pub struct AddCaseAttachmentInput {
+    #[serde(rename = "CaseNum")]
+    case_num: u32,
+    #[serde(rename = "FileName")]
+    file_name: String,
+    /// Base64 encoded file contents
+    #[serde(rename = "Content")]
+    content: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AddCaseAttachmentResponse {
+    #[serde(rename = "Error")]
+    error: bool,
+    #[serde(rename = "Message")]
+    message: Option<String>,
+    #[serde(rename = "AttachmentID")]
+    attachment_id: Option<i64>,
+    #[serde(rename = "FileName")]
+    file_name: Option<String>,
+}
+
+impl_epicor_response!(AddCaseAttachmentResponse);
+
 #[derive(Serialize, Debug)]
 pub struct UpdateQuoteInput {
     #[serde(rename = "CaseNum")]
@@ -123,22 +502,125 @@ pub struct UpdateQuoteResponse {
     #[serde(rename = "Error")]
     error: bool,
     #[serde(rename = "Message")]
-    message: String,
+    message: Option<String>,
+    // Older versions of the function library don't return the recalculated quote
+    #[serde(rename = "QuoteNum", default)]
+    quote_num: Option<u32>,
+    #[serde(rename = "UnitPrice", default)]
+    unit_price: Option<f64>,
+    #[serde(rename = "ExtendedPrice", default)]
+    extended_price: Option<f64>,
+}
+
+impl_epicor_response!(UpdateQuoteResponse);
+
+#[derive(Serialize, Debug)]
+pub struct UpdateCaseInput {
+    #[serde(rename = "CaseNum")]
+    case_num: u32,
+    #[serde(rename = "Qty", skip_serializing_if = "Option::is_none")]
+    qty: Option<f32>,
+    #[serde(rename = "UnitPrice", skip_serializing_if = "Option::is_none")]
+    unit_price: Option<f64>,
+    #[serde(rename = "PartNum", skip_serializing_if = "Option::is_none")]
+    part_num: Option<String>,
+    #[serde(rename = "RequestedDelivery", skip_serializing_if = "Option::is_none")]
+    requested_delivery: Option<String>,
+    #[serde(rename = "ExpectedDeliveryDate", skip_serializing_if = "Option::is_none")]
+    expected_delivery_date: Option<String>,
+}
+
+impl UpdateCaseInput {
+    pub fn new(
+        case_num: u32,
+        qty: Option<f32>,
+        unit_price: Option<f64>,
+        part_num: Option<String>,
+        requested_delivery: Option<NaiveDate>,
+        expected_delivery_date: Option<NaiveDate>,
+    ) -> Self {
+        Self {
+            case_num,
+            qty,
+            unit_price,
+            part_num,
+            requested_delivery: requested_delivery.map(epicor_datetime),
+            expected_delivery_date: expected_delivery_date.map(epicor_datetime),
+        }
+    }
+
+    /// Returns true if none of the updatable fields were provided
+    fn is_empty(&self) -> bool {
+        self.qty.is_none()
+            && self.unit_price.is_none()
+            && self.part_num.is_none()
+            && self.requested_delivery.is_none()
+            && self.expected_delivery_date.is_none()
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct SetCaseDatesInput {
+    #[serde(rename = "CaseNum")]
+    case_num: u32,
+    #[serde(rename = "RequestedDelivery", skip_serializing_if = "Option::is_none")]
+    requested_delivery: Option<String>,
+    #[serde(rename = "StartDate", skip_serializing_if = "Option::is_none")]
+    start_date: Option<String>,
+    #[serde(rename = "ExpectedDeliveryDate", skip_serializing_if = "Option::is_none")]
+    expected_delivery_date: Option<String>,
+}
+
+impl SetCaseDatesInput {
+    pub fn new(
+        case_num: u32,
+        requested_delivery: Option<NaiveDate>,
+        start_date: Option<NaiveDate>,
+        expected_delivery_date: Option<NaiveDate>,
+    ) -> Self {
+        Self {
+            case_num,
+            requested_delivery: requested_delivery.map(epicor_datetime),
+            start_date: start_date.map(epicor_datetime),
+            expected_delivery_date: expected_delivery_date.map(epicor_datetime),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SetCaseDatesResponse {
+    #[serde(rename = "Error")]
+    error: bool,
+    #[serde(rename = "Message")]
+    message: Option<String>,
+}
+
+impl_epicor_response!(SetCaseDatesResponse);
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UpdateCaseResponse {
+    #[serde(rename = "Error")]
+    error: bool,
+    #[serde(rename = "Message")]
+    message: Option<String>,
 }
 
+impl_epicor_response!(UpdateCaseResponse);
+
 #[derive(Serialize, Debug)]
 pub struct CompleteTaskInput {
     #[serde(rename = "CaseNum")]
     case_num: u32,
-    #[serde(rename = "AssignNextToName")]
-    assign_next_to_name: String,
+    /// Left out when completing a case's final task, which has no next task to assign
+    #[serde(rename = "AssignNextToName", skip_serializing_if = "Option::is_none")]
+    assign_next_to_name: Option<String>,
 }
 
 impl CompleteTaskInput {
-    pub fn new(case_num: u32, assign_next_to_name: &str) -> Self {
+    pub fn new(case_num: u32, assign_next_to_name: Option<&str>) -> Self {
         Self {
             case_num,
-            assign_next_to_name: assign_next_to_name.to_string(),
+            assign_next_to_name: assign_next_to_name.map(|name| name.to_string()),
         }
     }
 }
@@ -148,7 +630,7 @@ pub struct CompleteTaskResponse {
     #[serde(rename = "Error")]
     error: bool,
     #[serde(rename = "Message")]
-    message: String,
+    message: Option<String>,
     #[serde(rename = "HasActiveTask")]
     has_active_task: bool,
     #[serde(rename = "AuthorizedToCompleteTask")]
@@ -157,597 +639,2725 @@ pub struct CompleteTaskResponse {
     multiple_sales_rep_matches: bool,
     #[serde(rename = "NoSalesRepMatch")]
     no_sales_rep_match: bool,
+    /// Names of the sales reps that matched, when there was more than one
+    #[serde(rename = "SalesRepMatches", default)]
+    sales_rep_matches: Vec<String>,
+    /// Whether the completed task was the last one in the case's workflow
+    #[serde(rename = "IsFinalTask", default)]
+    is_final_task: bool,
+}
+
+impl CompleteTaskResponse {
+    /// Why Epicor didn't complete the task, when the response's flags say so. The flags only
+    /// count when Epicor reports an error, so a completed task is never taken for a refusal.
+    fn refusal(&self) -> Option<CompleteTaskError> {
+        if !self.error {
+            return None;
+        }
+
+        if !self.has_active_task {
+            return Some(CompleteTaskError::NoActiveTask);
+        }
+
+        if !self.authorized_to_complete_task {
+            return Some(CompleteTaskError::NotAuthorized);
+        }
+
+        if self.no_sales_rep_match {
+            return Some(CompleteTaskError::NoSalesRepMatch);
+        }
+
+        if self.multiple_sales_rep_matches {
+            return Some(CompleteTaskError::MultipleSalesRepMatches(
+                self.sales_rep_matches.clone(),
+            ));
+        }
+
+        None
+    }
+}
+
+// Refusals are reported by send_complete_task, so they keep their exit code
+impl_epicor_response!(CompleteTaskResponse, reported_by_caller: |response| {
+    response.refusal().is_some()
+});
+
+/// The reasons Epicor refuses to complete a task. Each has its own exit code so scripts can
+/// tell them apart from other failures, which exit with 1.
+#[derive(Debug)]
+pub enum CompleteTaskError {
+    NoActiveTask,
+    NotAuthorized,
+    NoSalesRepMatch,
+    MultipleSalesRepMatches(Vec<String>),
+}
+
+impl CompleteTaskError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CompleteTaskError::NoActiveTask => 3,
+            CompleteTaskError::NotAuthorized => 4,
+            CompleteTaskError::NoSalesRepMatch | CompleteTaskError::MultipleSalesRepMatches(_) => 5,
+        }
+    }
+}
+
+impl Display for CompleteTaskError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            CompleteTaskError::NoActiveTask => write!(
+                f,
+                "No active task to complete. The case may already be finished; check it with get-status."
+            ),
+            CompleteTaskError::NotAuthorized => write!(
+                f,
+                "You are not authorized to complete this task. Ask its assignee to complete it or reassign it to you."
+            ),
+            CompleteTaskError::NoSalesRepMatch => write!(
+                f,
+                "No sales rep matches that name. Check the spelling of --assign-to."
+            ),
+            CompleteTaskError::MultipleSalesRepMatches(sales_rep_matches) => {
+                write!(f, "{}", multiple_sales_rep_matches_message(sales_rep_matches))
+            }
+        }
+    }
+}
+
+impl Error for CompleteTaskError {}
+
+/// Lists the matching sales reps, when Epicor returns them, so that a more specific name can be picked
+fn multiple_sales_rep_matches_message(sales_rep_matches: &[String]) -> String {
+    if sales_rep_matches.is_empty() {
+        return "More than one sales rep matches that name. Use a more specific --assign-to.".to_string();
+    }
+
+    format!(
+        "More than one sales rep matches that name: {}. Use a more specific --assign-to.",
+        sales_rep_matches.join(", ")
+    )
 }
 
 #[derive(Serialize, Debug)]
-pub struct CaseStatusInput {
+pub struct SetCasePhaseInput {
     #[serde(rename = "CaseNum")]
     case_num: u32,
+    #[serde(rename = "WBSPhaseID")]
+    wbs_phase_id: String,
+    #[serde(rename = "WBSPhaseOp")]
+    wbs_phase_op: i32,
 }
 
-impl CaseStatusInput {
-    pub fn new(case_num: u32) -> Self {
-        Self { case_num }
+impl SetCasePhaseInput {
+    pub fn new(case_num: u32, wbs_phase_id: &str, wbs_phase_op: i32) -> Self {
+        Self {
+            case_num,
+            wbs_phase_id: wbs_phase_id.to_string(),
+            wbs_phase_op,
+        }
     }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
-pub struct CaseStatusResponse {
+pub struct SetCasePhaseResponse {
     #[serde(rename = "Error")]
-    pub error: bool,
-
+    error: bool,
     #[serde(rename = "Message")]
-    pub message: String,
-
-    #[serde(rename = "ProjectID")]
-    pub project_id: String,
+    message: Option<String>,
+}
 
-    #[serde(rename = "CaseDescription")]
-    pub case_description: String,
+impl_epicor_response!(SetCasePhaseResponse);
 
-    #[serde(rename = "PartNum")]
-    pub part_num: String,
+#[derive(Serialize, Debug)]
+pub struct SetEstimatedHoursInput {
+    #[serde(rename = "CaseNum")]
+    case_num: u32,
+    #[serde(rename = "EstimatedHours")]
+    estimated_hours: f64,
+}
+
+impl SetEstimatedHoursInput {
+    pub fn new(case_num: u32, estimated_hours: f64) -> Self {
+        Self {
+            case_num,
+            estimated_hours,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SetEstimatedHoursResponse {
+    #[serde(rename = "Error")]
+    error: bool,
+    #[serde(rename = "Message")]
+    message: Option<String>,
+}
+
+impl_epicor_response!(SetEstimatedHoursResponse);
+
+#[derive(Serialize, Debug)]
+pub struct ReassignInput {
+    #[serde(rename = "CaseNum")]
+    case_num: u32,
+    #[serde(rename = "AssignToName")]
+    assign_to_name: String,
+}
+
+impl ReassignInput {
+    pub fn new(case_num: u32, assign_to_name: &str) -> Self {
+        Self {
+            case_num,
+            assign_to_name: assign_to_name.to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ReassignResponse {
+    #[serde(rename = "Error")]
+    error: bool,
+    #[serde(rename = "Message")]
+    message: Option<String>,
+    #[serde(rename = "MultipleSalesRepMatches")]
+    multiple_sales_rep_matches: bool,
+    #[serde(rename = "NoSalesRepMatch")]
+    no_sales_rep_match: bool,
+    /// Names of the sales reps that matched, when there was more than one
+    #[serde(rename = "SalesRepMatches", default)]
+    sales_rep_matches: Vec<String>,
+}
+
+// Assignee problems are explained by reassign_case rather than with Epicor's generic message
+impl_epicor_response!(ReassignResponse, reported_by_caller: |response| {
+    response.no_sales_rep_match || response.multiple_sales_rep_matches
+});
+
+#[derive(Serialize, Debug)]
+pub struct CloseCaseInput {
+    #[serde(rename = "CaseNum")]
+    case_num: u32,
+    #[serde(rename = "Comment")]
+    comment: Option<String>,
+    /// Close the case even if it still has an active task
+    #[serde(rename = "Force")]
+    force: bool,
+}
+
+impl CloseCaseInput {
+    pub fn new(case_num: u32, comment: Option<&str>, force: bool) -> Self {
+        Self {
+            case_num,
+            comment: comment.map(|comment| comment.to_string()),
+            force,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CloseCaseResponse {
+    #[serde(rename = "Error")]
+    error: bool,
+    #[serde(rename = "Message")]
+    message: Option<String>,
+    #[serde(rename = "HasOpenTask", default)]
+    has_open_task: bool,
+}
+
+// An open task is reported by close_case, which knows the case number
+impl_epicor_response!(CloseCaseResponse, reported_by_caller: |response| response.has_open_task);
+
+#[derive(Serialize, Debug)]
+pub struct CreateCaseInput {
+    #[serde(rename = "CustID")]
+    customer: String,
+    #[serde(rename = "Description")]
+    description: String,
+    #[serde(rename = "ProjectID")]
+    project_id: String,
+    #[serde(rename = "PartNum")]
+    part_num: String,
+    #[serde(rename = "CaseOwner")]
+    case_owner: Option<String>,
+}
+
+impl CreateCaseInput {
+    pub fn new(
+        customer: String,
+        description: String,
+        project_id: String,
+        part_num: String,
+        case_owner: Option<String>,
+    ) -> Self {
+        Self {
+            customer,
+            description,
+            project_id,
+            part_num,
+            case_owner,
+        }
+    }
+
+    /// Returns the flags of the required fields that were left blank
+    fn blank_fields(&self) -> Vec<&'static str> {
+        [
+            ("--customer", &self.customer),
+            ("--description", &self.description),
+            ("--project", &self.project_id),
+            ("--part-num", &self.part_num),
+        ]
+        .into_iter()
+        .filter(|(_, value)| value.trim().is_empty())
+        .map(|(flag, _)| flag)
+        .collect()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CreateCaseResponse {
+    #[serde(rename = "Error")]
+    error: bool,
+    #[serde(rename = "Message")]
+    message: Option<String>,
+    #[serde(rename = "CaseNum", default)]
+    case_num: u32,
+}
+
+impl_epicor_response!(CreateCaseResponse);
+
+#[derive(Serialize, Debug)]
+pub struct ListCasesInput {
+    #[serde(rename = "AssignedTo")]
+    assigned_to: Option<String>,
+    #[serde(rename = "ProjectID")]
+    project_id: Option<String>,
+    #[serde(rename = "CaseOwner")]
+    case_owner: Option<String>,
+    #[serde(rename = "Limit")]
+    limit: u32,
+}
+
+impl ListCasesInput {
+    pub fn new(
+        assigned_to: Option<String>,
+        project_id: Option<String>,
+        case_owner: Option<String>,
+        limit: u32,
+    ) -> Self {
+        Self {
+            assigned_to,
+            project_id,
+            case_owner,
+            limit,
+        }
+    }
+}
+
+/// An empty result set may come back with Cases left out or null
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ListCasesResponse {
+    #[serde(rename = "Error")]
+    error: bool,
+    #[serde(rename = "Message")]
+    message: Option<String>,
+    #[serde(rename = "Cases", default, deserialize_with = "null_as_empty")]
+    cases: Vec<CaseSummary>,
+}
+
+impl_epicor_response!(ListCasesResponse);
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CaseSummary {
+    #[serde(rename = "CaseNum")]
+    case_num: u32,
+    #[serde(rename = "CaseDescription")]
+    case_description: String,
+    #[serde(rename = "CurrentTask")]
+    current_task: String,
+    #[serde(rename = "CurrentTaskAssignedTo")]
+    current_task_assigned_to: String,
+    #[serde(rename = "ExpectedDeliveryDate")]
+    expected_delivery_date: String,
+}
+
+impl CaseSummary {
+    /// Column names for CSV export, matching the Epicor field names
+    const CSV_HEADER: [&'static str; 5] = [
+        "CaseNum",
+        "CaseDescription",
+        "CurrentTask",
+        "CurrentTaskAssignedTo",
+        "ExpectedDeliveryDate",
+    ];
+
+    /// The case's values, in the same order as the table and CSV columns
+    fn fields(&self) -> [String; 5] {
+        [
+            self.case_num.to_string(),
+            self.case_description.clone(),
+            self.current_task.clone(),
+            self.current_task_assigned_to.clone(),
+            self.expected_delivery_date.clone(),
+        ]
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct CaseStatusInput {
+    #[serde(rename = "CaseNum")]
+    case_num: u32,
+}
+
+impl CaseStatusInput {
+    pub fn new(case_num: u32) -> Self {
+        Self { case_num }
+    }
+}
+
+/// Epicor leaves some columns out when they aren't populated, so those default to empty rather
+/// than failing to deserialize
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct CaseStatusResponse {
+    #[serde(rename = "Error")]
+    pub error: bool,
+
+    #[serde(rename = "Message", default)]
+    pub message: Option<String>,
+
+    #[serde(rename = "ProjectID")]
+    pub project_id: String,
+
+    #[serde(rename = "CaseDescription")]
+    pub case_description: String,
+
+    #[serde(rename = "PartNum")]
+    pub part_num: String,
 
     #[serde(rename = "Qty")]
     pub qty: f64,
 
-    #[serde(rename = "UnitPrice")]
-    pub unit_price: f64,
+    #[serde(rename = "UnitPrice")]
+    pub unit_price: f64,
+
+    #[serde(rename = "CaseOwner")]
+    pub case_owner: String,
+
+    #[serde(rename = "InternalContact", default)]
+    pub internal_contact: String,
+
+    #[serde(rename = "CaseContact", default)]
+    pub case_contact: String,
+
+    #[serde(rename = "CurrentTask")]
+    pub current_task: String,
+
+    #[serde(rename = "CurrentTaskAssignedTo")]
+    pub current_task_assigned_to: String,
+
+    #[serde(rename = "RequestedDelivery", default)]
+    pub requested_delivery: Option<String>,
+
+    #[serde(rename = "StartDate", default)]
+    pub start_date: Option<String>,
+
+    #[serde(rename = "ExpectedDeliveryDate", default)]
+    pub expected_delivery_date: Option<String>,
+
+    #[serde(rename = "Developer", default)]
+    pub developer: String,
+
+    #[serde(rename = "WBSPhaseID")]
+    pub wbs_phase_id: String,
+
+    #[serde(rename = "WBSPhaseOp")]
+    pub wbs_phase_op: i32,
+
+    #[serde(rename = "EstimatedHours")]
+    pub estimated_hours: f64,
+
+    #[serde(rename = "HoursScheduled")]
+    pub hours_scheduled: f64,
+
+    #[serde(rename = "HoursApplied")]
+    pub hours_applied: f64,
+
+    #[serde(rename = "BilledPercent")]
+    pub billed_percent: f64,
+}
+
+impl_epicor_response!(CaseStatusResponse);
+
+pub fn set_dry_run(dry_run: bool) {
+    DRY_RUN.store(dry_run, Ordering::Relaxed);
+}
+
+pub fn set_accept_invalid_certs(accept_invalid_certs: bool) {
+    ACCEPT_INVALID_CERTS.store(accept_invalid_certs, Ordering::Relaxed);
+}
+
+pub fn set_cache_ttl(cache_ttl_secs: u64) {
+    CACHE_TTL_SECS.store(cache_ttl_secs, Ordering::Relaxed);
+}
+
+pub fn set_date_format(date_format: &str) -> Result<()> {
+    // chrono panics when printing a date with an invalid format, so reject it up front
+    if StrftimeItems::new(date_format).any(|item| matches!(item, Item::Error)) {
+        return Err(anyhow!("Invalid --date-format '{}', expected e.g. %d/%m/%Y", date_format));
+    }
+
+    DATE_FORMAT
+        .set(date_format.to_string())
+        .map_err(|_| anyhow!("Date format already set"))
+}
+
+/// Reformats a date from Epicor, which sends ISO timestamps such as 2023-06-01T00:00:00.
+/// Missing dates come through empty, null or as 0001-01-01 and are shown as a dash.
+fn format_epicor_date(date: Option<&str>) -> String {
+    let date = date.unwrap_or_default().trim();
+
+    if date.is_empty() || date.starts_with("0001-01-01") {
+        return "—".to_string();
+    }
+
+    let date_format = DATE_FORMAT
+        .get()
+        .map(|date_format| date_format.as_str())
+        .unwrap_or(DEFAULT_DATE_FORMAT);
+
+    // Only the date part matters, whatever precision or offset the time has
+    let date_part = date.split('T').next().unwrap_or(date);
+
+    match NaiveDate::parse_from_str(date_part, "%Y-%m-%d") {
+        Ok(date) => date.format(date_format).to_string(),
+        // Show anything unexpected as is rather than hiding it
+        Err(_) => date.to_string(),
+    }
+}
+
+/// Formats a date as the timestamp Epicor expects, e.g. 2023-06-01T00:00:00
+fn epicor_datetime(date: NaiveDate) -> String {
+    date.format("%Y-%m-%dT00:00:00").to_string()
+}
+
+/// Reads the date part of an Epicor timestamp. Missing dates (empty or 0001-01-01) are None.
+fn parse_epicor_date(date: Option<&str>) -> Option<NaiveDate> {
+    let date = date?.trim();
+
+    if date.starts_with("0001-01-01") {
+        return None;
+    }
+
+    NaiveDate::parse_from_str(date.split('T').next()?, "%Y-%m-%d").ok()
+}
+
+/// Returns the Epicor REST version (e.g. v2) from EPICOR_API_VERSION, checking that it
+/// looks like one so it can't produce a malformed URL
+pub fn api_version() -> Result<String> {
+    let api_version = env::var("EPICOR_API_VERSION").unwrap_or(DEFAULT_API_VERSION.to_string());
+
+    check_api_version(&api_version)?;
+
+    Ok(api_version)
+}
+
+pub fn check_api_version(api_version: &str) -> Result<()> {
+    if !Regex::new(r"^v\d+$")?.is_match(api_version) {
+        return Err(anyhow!("Invalid Epicor API version '{}', expected e.g. v2", api_version));
+    }
+
+    Ok(())
+}
+
+/// Returns the Epicor company ID (e.g. 100) from EPICOR_COMPANY, checking that it's safe
+/// to put in a URL
+pub fn company() -> Result<String> {
+    let company = env::var("EPICOR_COMPANY").unwrap_or(DEFAULT_COMPANY.to_string());
+
+    check_company(&company)?;
+
+    Ok(company)
+}
+
+pub fn check_company(company: &str) -> Result<()> {
+    let is_valid = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_';
+
+    if company.is_empty() || !company.chars().all(is_valid) {
+        return Err(anyhow!("Invalid Epicor company '{}', expected e.g. 100", company));
+    }
+
+    Ok(())
+}
+
+/// Strips the trailing slashes and /api/vN people tend to paste with the base URL, which would
+/// otherwise be doubled up in request URLs. Errors unless it's an absolute http(s) URL.
+pub fn normalize_base_url(base_url: &str) -> Result<String> {
+    let api_suffix = Regex::new(r"(?i)/api/v\d+$").unwrap();
+
+    let trimmed = base_url.trim().trim_end_matches('/');
+    let normalized = api_suffix.replace(trimmed, "").trim_end_matches('/').to_string();
+
+    match Url::parse(&normalized) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") && url.has_host() => Ok(normalized),
+        _ => Err(anyhow!(
+            "Invalid Epicor base URL '{}', expected e.g. https://epicor.example.com/ERP",
+            base_url
+        )),
+    }
+}
+
+fn request_timeout_secs() -> u64 {
+    env::var("EPICOR_TIMEOUT_SECS")
+        .ok()
+        .and_then(|timeout| timeout.parse::<u64>().ok())
+        .unwrap_or(DEFAULT_TIMEOUT_SECS)
+}
+
+fn http_client(config: &EpicorConfig) -> Result<&'static Client> {
+    if let Some(client) = HTTP_CLIENT.get() {
+        return Ok(client);
+    }
+
+    let headers = default_headers(&config.api_key, &config.basic_auth)?;
+
+    trace!(headers = ?redacted_headers(&headers), "default Epicor request headers");
+
+    let client = client_builder()?.default_headers(headers).build()?;
+
+    Ok(HTTP_CLIENT.get_or_init(|| client))
+}
+
+/// Starts a client for Epicor with the request timeout and the TLS settings (EPICOR_CA_CERT and
+/// --danger-accept-invalid-certs), leaving the credentials to the caller
+pub fn client_builder() -> Result<ClientBuilder> {
+    let mut builder = Client::builder().timeout(Duration::from_secs(request_timeout_secs()));
+
+    // On-prem Epicor servers often use a certificate from an internal CA the system doesn't trust
+    if let Ok(ca_cert_path) = env::var("EPICOR_CA_CERT") {
+        let pem = fs::read(&ca_cert_path)
+            .map_err(|e| anyhow!("Failed to read EPICOR_CA_CERT {}: {}", ca_cert_path, e))?;
+        let certificate = Certificate::from_pem(&pem).map_err(|e| {
+            anyhow!("EPICOR_CA_CERT {} is not a PEM certificate: {}", ca_cert_path, e)
+        })?;
+
+        builder = builder.add_root_certificate(certificate);
+    }
+
+    if ACCEPT_INVALID_CERTS.load(Ordering::Relaxed) {
+        eprintln!(
+            "{} TLS certificate checks are disabled, so the connection to Epicor is not secure. Only use this against test environments.",
+            "Warning:".yellow().bold()
+        );
+
+        builder = builder.danger_accept_invalid_certs(true);
+    }
+
+    Ok(builder)
+}
+
+/// Headers sent with every Epicor request: the credentials and the JSON content type
+fn default_headers(api_key: &str, basic_auth: &str) -> Result<HeaderMap> {
+    let mut headers = HeaderMap::new();
+    headers.insert("X-API-Key", HeaderValue::from_str(api_key)?);
+    headers.insert(AUTHORIZATION, HeaderValue::from_str(basic_auth)?);
+    headers.insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static("application/json; charset=utf-8"),
+    );
+
+    Ok(headers)
+}
+
+/// Copies the headers for logging, masking the credentials
+fn redacted_headers(headers: &HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            // Header names are stored lowercase
+            let is_secret = name == AUTHORIZATION || name.as_str() == "x-api-key";
+
+            let value = if is_secret {
+                "[redacted]".to_string()
+            } else {
+                value.to_str().unwrap_or("[binary]").to_string()
+            };
+
+            (name.to_string(), value)
+        })
+        .collect()
+}
+
+/// 429 and gateway errors are returned while Epicor recycles its app pool, so they're worth retrying.
+/// Other statuses (e.g. 401 or 404) won't change on a retry.
+fn is_retryable_status(status: StatusCode) -> bool {
+    matches!(status.as_u16(), 429 | 502 | 503 | 504)
+}
+
+/// Backs off exponentially from the base delay, capped so a high retry count can't overflow
+fn retry_delay(attempt: u32) -> Duration {
+    let delay_ms = RETRY_BASE_DELAY_MS.saturating_mul(2u64.saturating_pow(attempt));
+
+    Duration::from_millis(delay_ms.min(MAX_RETRY_DELAY_MS))
+}
+
+async fn post_with_retry<T: Serialize + ?Sized>(
+    client: &Client,
+    url: &str,
+    body: &T,
+) -> Result<Response> {
+    let max_retries = env::var("EPICOR_MAX_RETRIES")
+        .ok()
+        .and_then(|max_retries| max_retries.parse::<u32>().ok())
+        .unwrap_or(DEFAULT_MAX_RETRIES)
+        .min(MAX_RETRIES);
+
+    let mut attempt = 0;
+
+    loop {
+        debug!(method = "POST", url, attempt, "sending Epicor request");
+
+        let started = Instant::now();
+        let result = client.post(url).json(body).send().await;
+        let elapsed_ms = started.elapsed().as_millis() as u64;
+
+        match &result {
+            Ok(resp) => debug!(
+                method = "POST",
+                url,
+                status = resp.status().as_u16(),
+                elapsed_ms,
+                "Epicor responded"
+            ),
+            Err(e) => debug!(
+                method = "POST",
+                url,
+                elapsed_ms,
+                error = %e,
+                "Epicor request failed"
+            ),
+        }
+
+        let retryable = match &result {
+            Ok(resp) => is_retryable_status(resp.status()),
+            Err(e) => e.is_timeout() || e.is_connect(),
+        };
+
+        if !retryable || attempt >= max_retries {
+            return result.map_err(|e| {
+                if e.is_timeout() {
+                    anyhow!("Epicor request timed out after {}s", request_timeout_secs())
+                } else if e.is_connect() {
+                    anyhow!("Could not connect to Epicor: {}", connection_error_message(&e))
+                } else {
+                    e.into()
+                }
+            });
+        }
+
+        // Back off exponentially before trying again
+        let delay = retry_delay(attempt);
+        debug!(delay_ms = delay.as_millis() as u64, "retrying Epicor request");
+        tokio::time::sleep(delay).await;
+
+        attempt += 1;
+    }
+}
+
+/// Shows the current task of a case and asks whether it should be completed
+pub async fn confirm_complete_task(
+    client: &EpicorClient,
+    case_num: u32,
+    assign_next_to_name: Option<&str>,
+) -> Result<bool> {
+    // There's nothing to confirm on a dry run, since nothing will be sent
+    let Some(case_status_response) = fetch_case_status(client, case_num).await? else {
+        return Ok(true);
+    };
+
+    let next_step = match assign_next_to_name {
+        Some(assign_next_to_name) => format!("assign to {}", assign_next_to_name),
+        None => "close the case if it's the final task".to_string(),
+    };
+
+    let confirmed = Confirm::new()
+        .with_prompt(format!(
+            "Complete task '{}' on case {} (currently assigned to {}) and {}?",
+            case_status_response.current_task,
+            case_num,
+            case_status_response.current_task_assigned_to,
+            next_step
+        ))
+        .default(false)
+        .interact()?;
+
+    Ok(confirmed)
+}
+
+/// Completes the current task of a case. With `close_if_final`, the case is also closed when
+/// Epicor reports that this was its final task.
+pub async fn send_complete_task(
+    client: &EpicorClient,
+    case_num: u32,
+    assign_next_to_name: Option<&str>,
+    comment: Option<&str>,
+    close_if_final: bool,
+) -> Result<()> {
+    let complete_task_input = CompleteTaskInput::new(case_num, assign_next_to_name);
+
+    let response: Option<CompleteTaskResponse> =
+        send_request(client, &complete_task_input, "Omni/CompleteTask").await?;
+
+    if let Some(refusal) = response.as_ref().and_then(|response| response.refusal()) {
+        return Err(refusal.into());
+    }
+
+    // Nothing is sent on a dry run, but the comment preview is still shown below
+    if response.is_some() {
+        invalidate_case_status(case_num);
+        status_println!("{}", "Task Completed".bright_green().bold());
+    }
+
+    // Only add the comment once the task has been completed so a failed completion
+    // doesn't leave a stray comment on the case.
+    if let Some(comment) = comment {
+        add_case_comment(client, case_num, comment, false)
+            .await
+            .map_err(|e| anyhow!("Task completed, but failed to add comment: {}", e))?;
+    }
+
+    let Some(response) = response else {
+        return Ok(());
+    };
+
+    if close_if_final {
+        if response.is_final_task {
+            close_case(client, CloseCaseInput::new(case_num, None, false))
+                .await
+                .map_err(|e| anyhow!("Task completed, but failed to close the case: {}", e))?;
+        } else {
+            status_println!("Case {} has more tasks, so it was left open", case_num);
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads a --from-file batch of cases, one case number per line or case,assign_to CSV lines.
+/// Blank lines, # comments and a header row are skipped. Cases without an assignee get the default.
+pub fn read_case_batch(
+    path: &Path,
+    default_assign_to: Option<&str>,
+) -> Result<Vec<(u32, String)>> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+
+    let mut cases = Vec::new();
+
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (case_field, assign_to_field) = match line.split_once(',') {
+            Some((case_field, assign_to_field)) => (case_field.trim(), assign_to_field.trim()),
+            None => (line, ""),
+        };
+
+        let case_num = match parse_case_number(case_field) {
+            Ok(case_num) => case_num,
+            // A CSV export usually starts with a header row
+            Err(_) if index == 0 => continue,
+            Err(e) => return Err(anyhow!("Line {} of {}: {}", index + 1, path.display(), e)),
+        };
+
+        let assign_to = match (assign_to_field, default_assign_to) {
+            ("", Some(default_assign_to)) => default_assign_to.to_string(),
+            ("", None) => {
+                return Err(anyhow!(
+                    "Line {} of {}: no assignee for case {} (add one to the line or pass --assign-to)",
+                    index + 1,
+                    path.display(),
+                    case_num
+                ));
+            }
+            (assign_to, _) => assign_to.to_string(),
+        };
+
+        cases.push((case_num, assign_to));
+    }
+
+    if cases.is_empty() {
+        return Err(anyhow!("No case numbers found in {}", path.display()));
+    }
+
+    Ok(cases)
+}
+
+/// Asks once whether the tasks on a whole --from-file batch should be completed
+pub fn confirm_complete_task_batch(case_count: usize, path: &Path) -> Result<bool> {
+    let confirmed = Confirm::new()
+        .with_prompt(format!(
+            "Complete the current task on {} cases from {}?",
+            case_count,
+            path.display()
+        ))
+        .default(false)
+        .interact()?;
+
+    Ok(confirmed)
+}
+
+/// Completes the current task on each case in turn, carrying on past failures unless
+/// `fail_fast` is set, then prints a summary. Fails if any of the cases failed.
+pub async fn complete_task_batch(
+    client: &EpicorClient,
+    cases: &[(u32, String)],
+    comment: Option<&str>,
+    fail_fast: bool,
+) -> Result<()> {
+    let mut results = Vec::new();
+
+    for (case_num, assign_to) in cases {
+        status_println!("Completing task on case {} and assigning to {}", case_num, assign_to);
+
+        let result = send_complete_task(client, *case_num, Some(assign_to), comment, false).await;
+        let is_err = result.is_err();
+
+        if let Err(e) = &result {
+            eprintln!("{} {}", format!("Case {} failed:", case_num).red().bold(), e);
+        }
+
+        results.push((*case_num, result));
+
+        if is_err && fail_fast {
+            break;
+        }
+    }
+
+    let rows: Vec<[String; 2]> = results
+        .iter()
+        .map(|(case_num, result)| {
+            let outcome = match result {
+                Ok(()) => "Completed".to_string(),
+                Err(e) => format!("Failed: {}", e),
+            };
+
+            [case_num.to_string(), outcome]
+        })
+        .collect();
+
+    print_table(["Case", "Result"], &rows);
+
+    let outcomes = results
+        .iter()
+        .map(|(case_num, result)| (*case_num, result.is_ok()));
+
+    batch_outcome(cases.len(), outcomes)?;
+
+    status_println!(
+        "{}",
+        format!("Completed the task on all {} cases", results.len()).bright_green().bold()
+    );
+
+    Ok(())
+}
+
+pub async fn reassign_case(
+    client: &EpicorClient,
+    case_num: u32,
+    assign_to_name: &str,
+) -> Result<()> {
+    let reassign_input = ReassignInput::new(case_num, assign_to_name);
+
+    let Some(response) =
+        send_request::<_, ReassignResponse>(client, &reassign_input, "Omni/ReassignCase").await?
+    else {
+        return Ok(());
+    };
+
+    if response.no_sales_rep_match {
+        return Err(anyhow!("No sales rep matches that name. Check the spelling of --assign-to."));
+    }
+
+    if response.multiple_sales_rep_matches {
+        return Err(anyhow!(multiple_sales_rep_matches_message(&response.sales_rep_matches)));
+    }
+
+    invalidate_case_status(case_num);
+
+    status_println!(
+        "{}",
+        format!("Case Reassigned to {}", assign_to_name).bright_green().bold(),
+    );
+
+    Ok(())
+}
+
+/// Moves a case to another WBS phase and operation, showing where it was moved from
+pub async fn move_case_phase(
+    client: &EpicorClient,
+    case_num: u32,
+    wbs_phase_id: &str,
+    wbs_phase_op: i32,
+) -> Result<()> {
+    // Read the current phase first so the change can be echoed back
+    let previous_status = fetch_case_status(client, case_num).await?;
+
+    let set_phase_input = SetCasePhaseInput::new(case_num, wbs_phase_id, wbs_phase_op);
+
+    let Some(_response) =
+        send_request::<_, SetCasePhaseResponse>(client, &set_phase_input, "Omni/SetCasePhase").await?
+    else {
+        return Ok(());
+    };
+
+    invalidate_case_status(case_num);
+
+    status_println!("{}", "Case Phase Updated".bright_green().bold());
+
+    let mut fields = Vec::new();
+
+    if let Some(previous_status) = previous_status {
+        fields.push((
+            "Previous Phase/Op",
+            format!("{} / {}", previous_status.wbs_phase_id, previous_status.wbs_phase_op),
+        ));
+    }
+
+    fields.push(("New Phase/Op", format!("{} / {}", wbs_phase_id, wbs_phase_op)));
+
+    print_fields(&fields);
+
+    Ok(())
+}
+
+/// Estimates above this many hours are most likely a typo, so they're flagged before sending
+const LARGE_ESTIMATE_HOURS: f64 = 1000.0;
+
+/// Sets the estimated hours of a case, then shows them next to the hours scheduled and applied
+pub async fn set_estimated_hours(
+    client: &EpicorClient,
+    case_num: u32,
+    estimated_hours: f64,
+) -> Result<()> {
+    if !estimated_hours.is_finite() || estimated_hours < 0.0 {
+        return Err(anyhow!("Estimated hours must be a non-negative number"));
+    }
+
+    if estimated_hours > LARGE_ESTIMATE_HOURS {
+        eprintln!(
+            "{} {} hours is an unusually large estimate. Check it's not a typo.",
+            "Warning:".yellow().bold(),
+            estimated_hours
+        );
+    }
+
+    let set_estimate_input = SetEstimatedHoursInput::new(case_num, estimated_hours);
+
+    let Some(_response) = send_request::<_, SetEstimatedHoursResponse>(
+        client,
+        &set_estimate_input,
+        "Omni/SetEstimatedHours",
+    )
+    .await?
+    else {
+        return Ok(());
+    };
+
+    invalidate_case_status(case_num);
+
+    status_println!("{}", "Estimated Hours Updated".bright_green().bold());
+
+    // Re-read the case so the estimate is shown as Epicor stored it
+    let Some(case_status_response) = fetch_case_status(client, case_num).await? else {
+        return Ok(());
+    };
+
+    print_fields(&[
+        ("Estimated Hours", case_status_response.estimated_hours.to_string()),
+        ("Hours Scheduled", case_status_response.hours_scheduled.to_string()),
+        ("Hours Applied", case_status_response.hours_applied.to_string()),
+    ]);
+
+    Ok(())
+}
+
+pub async fn close_case(client: &EpicorClient, close_case_input: CloseCaseInput) -> Result<()> {
+    let case_num = close_case_input.case_num;
+
+    let Some(response) =
+        send_request::<_, CloseCaseResponse>(client, &close_case_input, "Omni/CloseCase").await?
+    else {
+        return Ok(());
+    };
+
+    invalidate_case_status(case_num);
+
+    if response.has_open_task {
+        return Err(anyhow!(
+            "Case {} has an open task and cannot be closed (pass --force to close it anyway)",
+            case_num
+        ));
+    }
+
+    status_println!(
+        "{}",
+        format!("Case {} Closed", case_num).bright_green().bold(),
+    );
+
+    Ok(())
+}
+
+pub async fn create_case(
+    client: &EpicorClient,
+    create_case_input: CreateCaseInput,
+    json: bool,
+) -> Result<()> {
+    // Check every required field before sending, so all the omissions are reported at once
+    let blank_fields = create_case_input.blank_fields();
+
+    if !blank_fields.is_empty() {
+        return Err(anyhow!("Missing required case fields: {}", blank_fields.join(", ")));
+    }
+
+    let Some(response) =
+        send_request::<_, CreateCaseResponse>(client, &create_case_input, "Omni/CreateCase").await?
+    else {
+        return Ok(());
+    };
+
+    status_println!(
+        "{}",
+        format!("Case {} Created", response.case_num).bright_green().bold(),
+    );
+
+    // Show the new case so the result can be checked straight away
+    get_case_status(client, response.case_num, json).await
+}
+
+pub async fn list_cases(
+    client: &EpicorClient,
+    list_cases_input: ListCasesInput,
+    format: ListFormat,
+) -> Result<()> {
+    let limit = list_cases_input.limit as usize;
+
+    let Some(mut list_cases_response) =
+        send_request::<_, ListCasesResponse>(client, &list_cases_input, "Omni/ListCases").await?
+    else {
+        return Ok(());
+    };
+
+    // Don't rely on the backend honoring the limit, a huge result set would flood the terminal
+    list_cases_response.cases.truncate(limit);
+
+    match format {
+        // Print the raw cases so they can be piped into other tools
+        ListFormat::Json => {
+            data_println!("{}", to_json(&list_cases_response.cases)?)
+        }
+        ListFormat::Csv => print_case_csv(&list_cases_response.cases),
+        ListFormat::Table => print_case_list(&list_cases_response.cases),
+    }
+
+    Ok(())
+}
+
+/// Quotes a CSV field when it contains a comma, quote or line break, doubling any quotes inside it
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        return format!("\"{}\"", value.replace('"', "\"\""));
+    }
+
+    value.to_string()
+}
+
+fn print_case_csv(cases: &[CaseSummary]) {
+    data_println!("{}", CaseSummary::CSV_HEADER.join(","));
+
+    for case in cases {
+        let record: Vec<String> = case.fields().iter().map(|value| csv_field(value)).collect();
+        data_println!("{}", record.join(","));
+    }
+}
+
+fn print_case_list(cases: &[CaseSummary]) {
+    if cases.is_empty() {
+        data_println!("{}", "No cases found".bright_red());
+        return;
+    }
+
+    let headers = ["Case", "Description", "Current Task", "Assigned To", "Expected Delivery"];
+
+    let rows: Vec<[String; 5]> = cases
+        .iter()
+        .map(|case| {
+            let mut fields = case.fields();
+            fields[4] = format_epicor_date(Some(case.expected_delivery_date.as_str()));
+            fields
+        })
+        .collect();
+
+    print_table(headers, &rows);
+}
+
+/// Fetches the status of a case without printing it. Returns None on a dry run.
+pub async fn fetch_case_status(
+    client: &EpicorClient,
+    case_num: u32,
+) -> Result<Option<CaseStatusResponse>> {
+    if let Some(case_status_response) = cached_case_status(case_num) {
+        return Ok(Some(case_status_response));
+    }
+
+    let case_status_input = CaseStatusInput::new(case_num);
+
+    let response = send_request(client, &case_status_input, "Omni/GetCaseStatus").await?;
+
+    if let Some(case_status_response) = &response {
+        cache_case_status(case_num, case_status_response);
+    }
+
+    Ok(response)
+}
+
+fn case_status_cache_path(case_num: u32) -> Option<PathBuf> {
+    let cache_dir = cache_dir()?.join("case-status");
+
+    Some(cache_dir.join(format!("{}.json", case_num)))
+}
+
+/// Returns the case's status from the disk cache if --cache-ttl is set and it's fresh enough
+fn cached_case_status(case_num: u32) -> Option<CaseStatusResponse> {
+    let cache_ttl_secs = CACHE_TTL_SECS.load(Ordering::Relaxed);
+
+    if cache_ttl_secs == 0 {
+        return None;
+    }
+
+    let path = case_status_cache_path(case_num)?;
+    let age = fs::metadata(&path).ok()?.modified().ok()?.elapsed().ok()?;
+
+    if age > Duration::from_secs(cache_ttl_secs) {
+        return None;
+    }
+
+    let case_status_response = serde_json::from_slice(&fs::read(&path).ok()?).ok()?;
+
+    debug!(case_num, age_secs = age.as_secs(), "using cached case status");
+
+    Some(case_status_response)
+}
+
+fn cache_case_status(case_num: u32, case_status_response: &CaseStatusResponse) {
+    if CACHE_TTL_SECS.load(Ordering::Relaxed) == 0 {
+        return;
+    }
+
+    let Some(path) = case_status_cache_path(case_num) else {
+        return;
+    };
+
+    // A failed write only means the next read goes to Epicor
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+
+    if let Ok(contents) = serde_json::to_vec(case_status_response) {
+        let _ = fs::write(&path, contents);
+    }
+}
+
+/// Drops a case's cached status once the case has been changed, so it's never shown stale
+fn invalidate_case_status(case_num: u32) {
+    if let Some(path) = case_status_cache_path(case_num) {
+        let _ = fs::remove_file(path);
+    }
+}
+
+pub async fn get_case_status(client: &EpicorClient, case_num: u32, json: bool) -> Result<()> {
+    let Some(case_status_response) = fetch_case_status(client, case_num).await? else {
+        return Ok(());
+    };
+
+    // In JSON mode print the raw response so it can be piped into other tools
+    if json {
+        data_println!("{}", to_json(&case_status_response)?);
+        return Ok(());
+    }
+
+    print_case_status(&case_num, case_status_response);
+
+    Ok(())
+}
+
+/// Fetches the status of several cases, at most `concurrency` at a time, and prints them grouped
+/// by case in the order they were given. A case that fails is reported without stopping the others.
+pub async fn get_case_statuses(
+    client: &EpicorClient,
+    case_nums: &[u32],
+    concurrency: usize,
+    json: bool,
+    fail_fast: bool,
+) -> Result<()> {
+    let mut fetches = stream::iter(case_nums.iter().copied().enumerate())
+        .map(|(index, case_num)| async move {
+            (index, case_num, fetch_case_status(client, case_num).await)
+        })
+        .buffer_unordered(concurrency.max(1));
+
+    let mut results: Vec<(usize, u32, Result<Option<CaseStatusResponse>>)> = Vec::new();
+
+    while let Some(result) = fetches.next().await {
+        let is_err = result.2.is_err();
+
+        results.push(result);
+
+        // Dropping the stream cancels the requests still in flight
+        if is_err && fail_fast {
+            break;
+        }
+    }
+
+    // Responses arrive in whatever order Epicor answers them
+    results.sort_by_key(|(index, _, _)| *index);
+
+    let outcomes: Vec<(u32, bool)> = results
+        .iter()
+        .map(|(_, case_num, result)| (*case_num, result.is_ok()))
+        .collect();
+
+    if json {
+        let mut statuses = serde_json::Map::new();
+
+        for (_, case_num, result) in results {
+            let status = match result {
+                Ok(Some(case_status_response)) => serde_json::to_value(case_status_response)?,
+                Ok(None) => continue,
+                Err(e) => serde_json::json!({ "error": e.to_string() }),
+            };
+
+            statuses.insert(case_num.to_string(), status);
+        }
+
+        data_println!("{}", to_json(&statuses)?);
+    } else {
+        for (position, (_, case_num, result)) in results.into_iter().enumerate() {
+            if position > 0 {
+                data_println!();
+            }
+
+            match result {
+                Ok(Some(case_status_response)) => {
+                    print_case_status(&case_num, case_status_response)
+                }
+                Ok(None) => {}
+                Err(e) => print_fields(&[
+                    ("Case Number", case_num.to_string()),
+                    ("Error", e.to_string()),
+                ]),
+            }
+        }
+    }
+
+    batch_outcome(case_nums.len(), outcomes)
+}
+
+/// Lists which cases of a batch succeeded and which failed, given each case's number and
+/// whether it succeeded. Fails if any did, counting the cases a --fail-fast stop skipped.
+fn batch_outcome(total: usize, outcomes: impl IntoIterator<Item = (u32, bool)>) -> Result<()> {
+    let mut succeeded = Vec::new();
+    let mut failed = Vec::new();
+
+    for (case_num, ok) in outcomes {
+        if ok {
+            succeeded.push(case_num);
+        } else {
+            failed.push(case_num);
+        }
+    }
+
+    let case_list = |cases: &[u32]| {
+        cases
+            .iter()
+            .map(|case_num| case_num.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    if !succeeded.is_empty() {
+        status_println!("{} {}", "Succeeded:".bright_green().bold(), case_list(&succeeded));
+    }
+
+    if failed.is_empty() {
+        return Ok(());
+    }
+
+    status_println!("{} {}", "Failed:".red().bold(), case_list(&failed));
+
+    let skipped = total - succeeded.len() - failed.len();
+
+    if skipped > 0 {
+        return Err(anyhow!(
+            "{} of {} cases failed, {} were skipped by --fail-fast",
+            failed.len(),
+            total,
+            skipped
+        ));
+    }
+
+    Err(anyhow!("{} of {} cases failed", failed.len(), total))
+}
+
+/// Current task names Epicor reports once a case has no work left
+const TERMINAL_TASKS: [&str; 3] = ["complete", "completed", "closed"];
+
+fn is_terminal_task(task: &str) -> bool {
+    let task = task.trim();
+    task.is_empty() || TERMINAL_TASKS.iter().any(|t| task.eq_ignore_ascii_case(t))
+}
+
+/// Polls the status of a case and prints whenever the current task or its assignee changes.
+/// Failed checks only warn, so a transient Epicor outage doesn't end the watch.
+pub async fn watch_case_status(
+    client: &EpicorClient,
+    case_num: u32,
+    interval_secs: u64,
+    until_complete: bool,
+) -> Result<()> {
+    let interval = Duration::from_secs(interval_secs.max(1));
+
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+
+    status_println!(
+        "Watching case {} every {}s, press Ctrl-C to stop",
+        case_num,
+        interval.as_secs()
+    );
+
+    let mut last_task: Option<(String, String)> = None;
+
+    loop {
+        let result = tokio::select! {
+            _ = &mut ctrl_c => break,
+            result = fetch_case_status(client, case_num) => result,
+        };
+
+        match result {
+            Ok(Some(case_status_response)) => {
+                let task = (
+                    case_status_response.current_task,
+                    case_status_response.current_task_assigned_to,
+                );
+
+                if last_task.as_ref() != Some(&task) {
+                    print_task_change(last_task.as_ref(), &task);
+                }
+
+                if until_complete && is_terminal_task(&task.0) {
+                    status_println!("Case {} has no remaining tasks", case_num);
+                    return Ok(());
+                }
+
+                last_task = Some(task);
+            }
+            // Nothing is sent on a dry run, so there is nothing to watch
+            Ok(None) => return Ok(()),
+            Err(e) => eprintln!(
+                "{} Failed to get case status ({}), trying again in {}s",
+                "Warning:".yellow().bold(),
+                e,
+                interval.as_secs()
+            ),
+        }
+
+        tokio::select! {
+            _ = &mut ctrl_c => break,
+            _ = tokio::time::sleep(interval) => {}
+        }
+    }
+
+    status_println!("Stopped watching case {}", case_num);
+
+    Ok(())
+}
+
+/// Prints the current task and assignee, highlighting whichever changed since the last check
+fn print_task_change(previous: Option<&(String, String)>, current: &(String, String)) {
+    let timestamp = chrono::Local::now().format("%H:%M:%S");
+
+    let highlight = |label: &str, old: Option<&String>, new: &String| match old {
+        Some(old) if old != new => format!(
+            "{} {} -> {}",
+            label.red().bold().underline(),
+            old.dimmed(),
+            new.bright_green().bold()
+        ),
+        _ => format!("{} {}", label.red().bold().underline(), new),
+    };
+
+    data_println!(
+        "[{}] {} | {}",
+        timestamp,
+        highlight("Current Task:", previous.map(|p| &p.0), &current.0),
+        highlight("Assigned To:", previous.map(|p| &p.1), &current.1)
+    );
+}
+
+fn print_case_status(case_num: &u32, case_status_response: CaseStatusResponse) {
+    print_fields(&[
+        ("Case Number", case_num.to_string()),
+        ("Case Owner", case_status_response.case_owner),
+        ("Case Contact", case_status_response.case_contact),
+        ("Internal Contact", case_status_response.internal_contact),
+        ("Case Description", case_status_response.case_description),
+        ("Project", case_status_response.project_id),
+        ("Part Num", case_status_response.part_num),
+        ("Unit Price", case_status_response.unit_price.to_string()),
+        ("Quantity", case_status_response.qty.to_string()),
+        ("Phase", case_status_response.wbs_phase_id),
+        ("Op", case_status_response.wbs_phase_op.to_string()),
+        ("Current Task", case_status_response.current_task),
+        ("Assigned To", case_status_response.current_task_assigned_to),
+        ("Case Developer", case_status_response.developer),
+        ("Request Date", format_epicor_date(case_status_response.requested_delivery.as_deref())),
+        ("Start Date", format_epicor_date(case_status_response.start_date.as_deref())),
+        (
+            "Expected Delivery Date",
+            format_epicor_date(case_status_response.expected_delivery_date.as_deref()),
+        ),
+        ("Estimated Hours", case_status_response.estimated_hours.to_string()),
+        ("Hours Scheduled", case_status_response.hours_scheduled.to_string()),
+        ("Hours Applied", case_status_response.hours_applied.to_string()),
+        ("Billed Percent", case_status_response.billed_percent.to_string()),
+    ]);
+}
+
+/// Builds the link to a case in the Epicor web client from EPICOR_CASE_URL_TEMPLATE, filling in
+/// the {case}, {company} and {base_url} placeholders. The link differs between Epicor versions
+/// and hosting setups, so there's no default.
+pub fn case_url(config: &EpicorConfig, case_num: u32) -> Result<String> {
+    let template = env::var("EPICOR_CASE_URL_TEMPLATE").map_err(|_| {
+        anyhow!(
+            "EPICOR_CASE_URL_TEMPLATE is not set. Set it to the link of a case in the Epicor web client, with {{case}} in place of the case number (and optionally {{company}} and {{base_url}})"
+        )
+    })?;
+
+    if !template.contains("{case}") {
+        return Err(anyhow!(
+            "EPICOR_CASE_URL_TEMPLATE has no {{case}} placeholder for the case number"
+        ));
+    }
+
+    let url = template
+        .replace("{case}", &case_num.to_string())
+        .replace("{company}", &config.company)
+        .replace("{base_url}", &config.base_url);
+
+    Ok(url)
+}
+
+/// Prints the Epicor web link for a case, opening it in the default browser when asked
+pub fn open_case_url(client: &EpicorClient, case_num: u32, open: bool) -> Result<()> {
+    let url = case_url(&client.config, case_num)?;
+
+    data_println!("{}", url);
+
+    if open {
+        webbrowser::open(&url).map_err(|e| anyhow!("Failed to open {}: {}", url, e))?;
+    }
+
+    Ok(())
+}
+
+pub async fn update_case_quote(
+    client: &EpicorClient,
+    case_num: u32,
+    new_quantity: f32,
+    allow_zero: bool,
+) -> Result<()> {
+    // A zero or negative quote quantity is almost always a typo
+    if new_quantity <= 0.0 && !allow_zero {
+        return Err(anyhow!(
+            "New quantity must be greater than 0 (pass --allow-zero if this is intended)"
+        ));
+    }
+
+    let update_quote_input = UpdateQuoteInput::new(case_num, new_quantity);
+
+    let Some(response) =
+        send_request::<_, UpdateQuoteResponse>(client, &update_quote_input, "Omni/UpdateCaseQuote").await?
+    else {
+        return Ok(());
+    };
+
+    invalidate_case_status(case_num);
+
+    status_println!(
+        "{}",
+        "Quote Updated and Attached to Case".bright_green().bold(),
+    );
+
+    // Show the recalculated pricing so it can be checked without a follow-up get-status
+    let mut fields = Vec::new();
+
+    if let Some(quote_num) = response.quote_num {
+        fields.push(("Quote Number", quote_num.to_string()));
+    }
+
+    if let Some(unit_price) = response.unit_price {
+        fields.push(("Unit Price", format!("{:.2}", unit_price)));
+    }
+
+    if let Some(extended_price) = response.extended_price {
+        fields.push(("Extended Price", format!("{:.2}", extended_price)));
+    }
+
+    print_fields(&fields);
+
+    Ok(())
+}
+
+pub async fn update_case(client: &EpicorClient, update_case_input: UpdateCaseInput) -> Result<()> {
+    // Only the provided fields are sent, so there has to be at least one
+    if update_case_input.is_empty() {
+        return Err(anyhow!(
+            "Nothing to update. Provide at least one of --qty, --unit-price, --part-num, --requested-delivery or --expected-delivery"
+        ));
+    }
+
+    let Some(_response) =
+        send_request::<_, UpdateCaseResponse>(client, &update_case_input, "Omni/UpdateCase").await?
+    else {
+        return Ok(());
+    };
+
+    invalidate_case_status(update_case_input.case_num);
+
+    status_println!("{}", "Case Updated".bright_green().bold());
+
+    Ok(())
+}
+
+/// Sets any of a case's requested delivery, start and expected delivery dates, showing each date
+/// before and after the change
+pub async fn set_case_dates(
+    client: &EpicorClient,
+    case_num: u32,
+    requested_delivery: Option<NaiveDate>,
+    start_date: Option<NaiveDate>,
+    expected_delivery_date: Option<NaiveDate>,
+) -> Result<()> {
+    // Only the provided dates are sent, so there has to be at least one
+    if requested_delivery.is_none() && start_date.is_none() && expected_delivery_date.is_none() {
+        return Err(anyhow!(
+            "Nothing to update. Provide at least one of --requested, --start or --expected"
+        ));
+    }
+
+    // The current dates are shown next to the new ones, and stand in for whichever of the start
+    // and expected delivery dates isn't being changed
+    let previous_status = fetch_case_status(client, case_num).await?;
+
+    let current_date = |date: fn(&CaseStatusResponse) -> Option<&str>| {
+        previous_status
+            .as_ref()
+            .and_then(|previous_status| parse_epicor_date(date(previous_status)))
+    };
+
+    let start = start_date.or(current_date(|status| status.start_date.as_deref()));
+    let expected = expected_delivery_date
+        .or(current_date(|status| status.expected_delivery_date.as_deref()));
+
+    if let (Some(start), Some(expected)) = (start, expected) {
+        if start > expected {
+            return Err(anyhow!(
+                "The start date {} is after the expected delivery date {}",
+                start,
+                expected
+            ));
+        }
+    }
+
+    let set_dates_input =
+        SetCaseDatesInput::new(case_num, requested_delivery, start_date, expected_delivery_date);
+
+    let Some(_response) =
+        send_request::<_, SetCaseDatesResponse>(client, &set_dates_input, "Omni/SetCaseDates").await?
+    else {
+        return Ok(());
+    };
+
+    invalidate_case_status(case_num);
+
+    status_println!("{}", "Case Dates Updated".bright_green().bold());
+
+    let Some(previous_status) = previous_status else {
+        return Ok(());
+    };
+
+    let dates = [
+        ("Requested Delivery", previous_status.requested_delivery, requested_delivery),
+        ("Start Date", previous_status.start_date, start_date),
+        ("Expected Delivery", previous_status.expected_delivery_date, expected_delivery_date),
+    ];
+
+    let fields: Vec<(&str, String)> = dates
+        .into_iter()
+        .map(|(label, before, after)| {
+            let before = format_epicor_date(before.as_deref());
+            let after = match after {
+                Some(after) => format_epicor_date(Some(&epicor_datetime(after))),
+                None => before.clone(),
+            };
+
+            (label, format!("{} → {}", before, after))
+        })
+        .collect();
+
+    print_fields(&fields);
+
+    Ok(())
+}
+
+/// Adds a comment to a case. With `dedupe`, nothing is added if the case's last comment already
+/// has the same text, e.g. when a script retries after a request that actually went through.
+pub async fn add_case_comment(
+    client: &EpicorClient,
+    case_num: u32,
+    comment: &str,
+    dedupe: bool,
+) -> Result<()> {
+    if dedupe {
+        let last_comment = fetch_last_case_comment(client, case_num)
+            .await?
+            .and_then(|last_comment_response| last_comment_response.comment);
+
+        if last_comment.is_some_and(|last_comment| last_comment.trim() == comment.trim()) {
+            status_println!("Comment already present, skipping");
+            return Ok(());
+        }
+    }
+
+    let add_comment_input = AddCaseCommentInput::new(case_num, comment);
+
+    let Some(_response) =
+        send_request::<_, AddCaseCommentResponse>(client, &add_comment_input, "Omni/AddCaseComment").await?
+    else {
+        return Ok(());
+    };
+
+    status_println!(
+        "{}",
+        "Comment Added to Case".bright_green().bold(),
+    );
+
+    Ok(())
+}
+
+pub async fn edit_last_comment(client: &EpicorClient, case_num: u32, comment: &str) -> Result<()> {
+    let edit_comment_input = EditLastCommentInput::new(case_num, comment);
+
+    let Some(response) =
+        send_request::<_, EditLastCommentResponse>(client, &edit_comment_input, "Omni/EditLastComment").await?
+    else {
+        return Ok(());
+    };
+
+    if response.no_comments {
+        return Err(anyhow!("Case {} has no comments to edit", case_num));
+    }
+
+    status_println!("{}", "Last Comment Updated".bright_green().bold());
+
+    Ok(())
+}
+
+pub async fn add_case_attachment(client: &EpicorClient, case_num: u32, file: &Path) -> Result<()> {
+    let file_name = file
+        .file_name()
+        .and_then(|file_name| file_name.to_str())
+        .ok_or(anyhow!("{} is not a file", file.display()))?
+        .to_string();
+
+    // The whole file is sent in one JSON body, so refuse files Epicor would choke on
+    // before reading them into memory
+    let file_size = fs::metadata(file)
+        .map_err(|e| anyhow!("Failed to read {}: {}", file.display(), e))?
+        .len();
+
+    if file_size > MAX_ATTACHMENT_BYTES {
+        return Err(anyhow!(
+            "{} is {:.1} MB, attachments can be at most {} MB",
+            file.display(),
+            file_size as f64 / (1024.0 * 1024.0),
+            MAX_ATTACHMENT_BYTES / (1024 * 1024)
+        ));
+    }
+
+    let contents =
+        fs::read(file).map_err(|e| anyhow!("Failed to read {}: {}", file.display(), e))?;
+
+    let add_attachment_input = AddCaseAttachmentInput {
+        case_num,
+        file_name,
+        content: general_purpose::STANDARD.encode(contents),
+    };
+
+    let Some(response) =
+        send_request::<_, AddCaseAttachmentResponse>(client, &add_attachment_input, "Omni/AddCaseAttachment").await?
+    else {
+        return Ok(());
+    };
+
+    let file_name = response
+        .file_name
+        .unwrap_or(add_attachment_input.file_name);
+
+    match response.attachment_id {
+        Some(attachment_id) => status_println!(
+            "{} {} (attachment {})",
+            "Attached to Case:".bright_green().bold(),
+            file_name,
+            attachment_id
+        ),
+        None => status_println!("{} {}", "Attached to Case:".bright_green().bold(), file_name),
+    }
+
+    Ok(())
+}
+
+/// Fetches the last comment on a case without printing it. Returns None on a dry run.
+async fn fetch_last_case_comment(
+    client: &EpicorClient,
+    case_num: u32,
+) -> Result<Option<GetLastCommentResponse>> {
+    let last_case_comment_input = GetLastCommentInput { case_num };
+
+    send_request(client, &last_case_comment_input, "Omni/GetLastComment").await
+}
+
+pub async fn get_last_case_comment(client: &EpicorClient, case_num: u32) -> Result<()> {
+    let Some(last_comment_response) = fetch_last_case_comment(client, case_num).await? else {
+        return Ok(());
+    };
+
+    data_println!("{}", "Last Comment".bright_green().bold().underline());
+
+    data_println!(
+        "{}",
+        last_comment_response.comment.unwrap_or("No comments".to_string()).bright_red(),
+    );
+
+    Ok(())
+}
+
+pub async fn get_case_comments(
+    client: &EpicorClient,
+    case_num: u32,
+    json: bool,
+    ai: bool,
+    limit: Option<usize>,
+    since: Option<NaiveDate>,
+) -> Result<()> {
+    let get_comments_input = GetCommentsInput { case_num };
+
+    let Some(mut comments_response) =
+        send_request::<_, GetCommentsResponse>(client, &get_comments_input, "Omni/GetCaseComments").await?
+    else {
+        return Ok(());
+    };
+
+    // Epicor returns ISO timestamps, so sorting the strings sorts the comments chronologically
+    comments_response
+        .comments
+        .sort_by(|a, b| a.created_on.cmp(&b.created_on));
+
+    // The function returns the whole thread, so narrow it down here
+    let total = comments_response.comments.len();
+
+    if let Some(since) = since {
+        // A timestamp on the day itself sorts after the bare date
+        let since = since.format("%Y-%m-%d").to_string();
+        comments_response
+            .comments
+            .retain(|comment| comment.created_on >= since);
+    }
+
+    if let Some(limit) = limit {
+        let skipped = comments_response.comments.len().saturating_sub(limit);
+        comments_response.comments.drain(..skipped);
+    }
 
-    #[serde(rename = "CaseOwner")]
-    pub case_owner: String,
+    // In JSON mode print the raw response so it can be piped into other tools
+    if json {
+        data_println!("{}", to_json(&comments_response)?);
+        return Ok(());
+    }
 
-    #[serde(rename = "InternalContact")]
-    pub internal_contact: String,
+    if comments_response.comments.len() < total {
+        status_println!("Showing {} of {} comments", comments_response.comments.len(), total);
+    }
 
-    #[serde(rename = "CaseContact")]
-    pub case_contact: String,
+    // The AI summary is best effort, the plain listing is always available as a fallback
+    if ai && !comments_response.comments.is_empty() {
+        match summarize_comments(&comment_thread(&comments_response.comments)).await {
+            Ok(summary) => {
+                data_println!("{}", "Comment Summary".bright_green().bold().underline());
+                data_println!("{}", summary);
+                return Ok(());
+            }
+            Err(e) => {
+                eprintln!(
+                    "{} Could not summarize comments ({}). Showing all comments instead.",
+                    "Warning:".yellow().bold(),
+                    e
+                );
+            }
+        }
+    }
 
-    #[serde(rename = "CurrentTask")]
-    pub current_task: String,
+    print_case_comments(&comments_response.comments);
 
-    #[serde(rename = "CurrentTaskAssignedTo")]
-    pub current_task_assigned_to: String,
+    Ok(())
+}
 
-    #[serde(rename = "RequestedDelivery")]
-    pub requested_delivery: String,
+/// Formats the comments as a plain text transcript to send to OpenAI
+fn comment_thread(comments: &[CaseComment]) -> String {
+    comments
+        .iter()
+        .map(|comment| {
+            format!(
+                "{} ({}):\n{}",
+                comment.author, comment.created_on, comment.comment
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
 
-    #[serde(rename = "StartDate")]
-    pub start_date: String,
+fn print_case_comments(comments: &[CaseComment]) {
+    data_println!("{}", "Case Comments".bright_green().bold().underline());
 
-    #[serde(rename = "ExpectedDeliveryDate")]
-    pub expected_delivery_date: String,
+    if comments.is_empty() {
+        data_println!("{}", "No comments".bright_red());
+        return;
+    }
 
-    #[serde(rename = "Developer")]
-    pub developer: String,
+    for comment in comments {
+        data_println!(
+            "{} {}",
+            comment.author.red().bold().underline(),
+            comment.created_on
+        );
+        data_println!("{}", comment.comment);
+        data_println!();
+    }
+}
 
-    #[serde(rename = "WBSPhaseID")]
-    pub wbs_phase_id: String,
+pub async fn send_time_entry(client: &EpicorClient, time_entry: TimeEntry) -> Result<()> {
+    // Project labor has to be booked against a project, so catch this before Epicor does
+    if time_entry.labor_type == LaborType::Project && time_entry.project_id.is_none() {
+        return Err(anyhow!("Project labor requires a project id (--project-id)"));
+    }
 
-    #[serde(rename = "WBSPhaseOp")]
-    pub wbs_phase_op: i32,
+    // Epicor rejects labor transactions without a plant
+    if time_entry.plant_id.is_none() {
+        return Err(anyhow!(
+            "Time entries need a plant. Pass --plant or set EPICOR_DEFAULT_PLANT (or epicor_default_plant in your profile)."
+        ));
+    }
 
-    #[serde(rename = "EstimatedHours")]
-    pub estimated_hours: f64,
+    let add_time_entry_input = AddTimeEntryInput::from(time_entry);
 
-    #[serde(rename = "HoursScheduled")]
-    pub hours_scheduled: f64,
+    let Some(_response) =
+        send_request::<_, AddTimeEntryResponse>(client, &add_time_entry_input, "Omni/AddTimeEntry").await?
+    else {
+        return Ok(());
+    };
 
-    #[serde(rename = "HoursApplied")]
-    pub hours_applied: f64,
+    status_println!(
+        "{}",
+        "Time Entry Added".bright_green().bold(),
+    );
 
-    #[serde(rename = "BilledPercent")]
-    pub billed_percent: f64,
+    Ok(())
 }
 
-pub async fn send_complete_task(case_num: u32, assign_next_to_name: &str) -> Result<()> {
-    // Retrieve environment variables
-    let api_key = env::var("EPICOR_API_KEY").map_err(|_| anyhow!("EPICOR_API_KEY must be set"))?;
-    let basic_auth =
-        env::var("EPICOR_BASIC_AUTH").map_err(|_| anyhow!("EPICOR_BASIC_AUTH must be set"))?;
-    let base_url =
-        env::var("EPICOR_BASE_URL").map_err(|_| anyhow!("EPICOR_BASE_URL must be set"))?;
-
-    // Prepare the HTTP client.
-    let client = Client::new();
+pub async fn list_time_entries(
+    client: &EpicorClient,
+    employee_num: u32,
+    date: NaiveDate,
+    json: bool,
+) -> Result<()> {
+    let list_time_entries_input = ListTimeEntriesInput {
+        employee_num,
+        date: date.format("%Y-%m-%d").to_string(),
+    };
 
-    // Prepare the JSON payload.
-    let complete_task_input = CompleteTaskInput {
-        case_num,
-        assign_next_to_name: assign_next_to_name.to_string(),
+    let Some(list_time_entries_response) =
+        send_request::<_, ListTimeEntriesResponse>(client, &list_time_entries_input, "Omni/ListTimeEntries").await?
+    else {
+        return Ok(());
     };
 
-    // Prepare the headers.
-    let mut headers = HeaderMap::new();
-    headers.insert("X-API-Key", HeaderValue::from_str(&api_key)?);
-    headers.insert(AUTHORIZATION, HeaderValue::from_str(&basic_auth)?);
-    headers.insert(
-        CONTENT_TYPE,
-        HeaderValue::from_static("application/json; charset=utf-8"),
+    // In JSON mode print the raw entries so they can be piped into other tools
+    if json {
+        data_println!("{}", to_json(&list_time_entries_response.time_entries)?);
+        return Ok(());
+    }
+
+    print_time_entries(&date, &list_time_entries_response.time_entries);
+
+    Ok(())
+}
+
+fn print_time_entries(date: &NaiveDate, time_entries: &[TimeEntrySummary]) {
+    if time_entries.is_empty() {
+        data_println!("{}", format!("No time entries for {}", date).bright_red());
+        return;
+    }
+
+    let headers = ["Project", "Phase", "Operation", "Labor Type", "Hours"];
+
+    let rows: Vec<[String; 5]> = time_entries
+        .iter()
+        .map(|entry| {
+            [
+                entry.project_id.clone().unwrap_or_default(),
+                entry.wbs_phase_id.clone().unwrap_or_default(),
+                entry.operation.map(|op| op.to_string()).unwrap_or_default(),
+                entry.labor_type.clone(),
+                format!("{:.2}", entry.hours),
+            ]
+        })
+        .collect();
+
+    print_table(headers, &rows);
+
+    let total_hours: f64 = time_entries.iter().map(|entry| entry.hours).sum();
+    data_println!(
+        "{} {:.2}",
+        format!("Total for {}:", date).red().bold(),
+        total_hours
     );
+}
 
-    // Construct the URL
-    // TODO: Make company dynamic
-    let url = format!("{}/api/v2/efx/100/Omni/CompleteTask", base_url);
+/// Checks that Epicor is reachable and accepts the configured credentials without touching any
+/// case, reporting the latency and which of the base URL, API key and basic auth look valid
+pub async fn ping(client: &EpicorClient) -> Result<()> {
+    let config = &client.config;
+    let base_url = &config.base_url;
+
+    // The OData service root is a cheap authenticated GET that doesn't read any records
+    let url = format!(
+        "{}/api/{}/odata/{}/",
+        base_url.trim_end_matches('/'),
+        config.api_version,
+        config.company
+    );
 
-    // Send the request and get the response.
-    let resp: Response = client
-        .post(&url)
-        .headers(headers)
-        .json(&complete_task_input)
-        .send()
-        .await?;
+    if DRY_RUN.load(Ordering::Relaxed) {
+        eprintln!("{} GET {}", "Dry Run:".yellow().bold(), url);
+        return Ok(());
+    }
 
-    // Check to see if the response was successful.
-    if !resp.status().is_success() {
-        // if the error is 404, this means that the function library is likely not published
-        if resp.status().as_u16() == 404 {
-            return Err(anyhow!(
-                "Error: {}",
-                "The Omni function library is not published in Epicor. Please publish the function library and try again."
-            ));
+    debug!(method = "GET", url, "sending Epicor request");
+
+    let spinner = Spinner::start("Contacting Epicor…");
+    let started = Instant::now();
+    let result = client.http.get(&url).send().await;
+    let elapsed_ms = started.elapsed().as_millis();
+    drop(spinner);
+
+    let resp = match result {
+        Ok(resp) => resp,
+        Err(e) => {
+            print_ping_check("Base URL:", Some(false), &connection_error_message(&e));
+            print_ping_check("API Key:", None, "not checked");
+            print_ping_check("Basic Auth:", None, "not checked");
+            return Err(anyhow!("Could not reach Epicor at {}", base_url));
         }
-        return Err(anyhow!("Error: {}", resp.status()));
-    }
+    };
 
-    // Deserialize the response.
-    let complete_task_response: CompleteTaskResponse = resp.json().await?;
+    let status = resp.status();
+    let body = resp.text().await.unwrap_or_default();
 
-    // Check for errors.
-    if complete_task_response.error {
-        return Err(anyhow!("Error: {}", complete_task_response.message));
+    let epicor_message = serde_json::from_str::<EpicorError>(&body)
+        .ok()
+        .and_then(|epicor_error| epicor_error.message())
+        .unwrap_or(status.to_string());
+
+    // Epicor answers 401 for a bad API key and for bad basic auth, naming the key if it's at fault.
+    // Any other failure (e.g. a 403 from a missing permission) says nothing certain about either,
+    // so only its status is reported.
+    let api_key_rejected = epicor_message.to_lowercase().contains("api key");
+
+    let (api_key, basic_auth) = match status.as_u16() {
+        200..=299 => (Some(true), Some(true)),
+        401 if api_key_rejected => (Some(false), None),
+        401 => (Some(true), Some(false)),
+        _ => (None, None),
+    };
+
+    let base_url_detail = match status.as_u16() {
+        404 => format!(
+            "{} has no Epicor REST API (check the server and instance name)",
+            base_url
+        ),
+        _ => format!("{} ({} ms)", base_url, elapsed_ms),
+    };
+
+    let detail = |valid: Option<bool>| match valid {
+        Some(true) => "accepted".to_string(),
+        Some(false) => epicor_message.clone(),
+        None if status.as_u16() == 401 => "not checked".to_string(),
+        None => epicor_message.clone(),
+    };
+
+    print_ping_check("Base URL:", Some(status.as_u16() != 404), &base_url_detail);
+    print_ping_check("API Key:", api_key, &detail(api_key));
+    print_ping_check("Basic Auth:", basic_auth, &detail(basic_auth));
+
+    if !status.is_success() {
+        return Err(anyhow!("Epicor returned {}", status));
     }
 
+    status_println!("{}", format!("Epicor responded in {} ms", elapsed_ms).bright_green().bold());
+
     Ok(())
 }
 
-pub async fn get_case_status(case_num: u32) -> Result<()> {
-    // Retrieve environment variables
-    let api_key = env::var("EPICOR_API_KEY").map_err(|_| anyhow!("EPICOR_API_KEY must be set"))?;
-    let basic_auth =
-        env::var("EPICOR_BASIC_AUTH").map_err(|_| anyhow!("EPICOR_BASIC_AUTH must be set"))?;
-    let base_url =
-        env::var("EPICOR_BASE_URL").map_err(|_| anyhow!("EPICOR_BASE_URL must be set"))?;
+fn print_ping_check(label: &str, valid: Option<bool>, detail: &str) {
+    let result = match valid {
+        Some(true) => "ok".bright_green().bold(),
+        Some(false) => "failed".red().bold(),
+        None => "unknown".yellow().bold(),
+    };
 
-    // Prepare the HTTP client.
-    let client = Client::new();
+    data_println!("{:<12} {:<8} {}", label, result, detail);
+}
 
-    // Prepare the JSON payload.
-    let complete_task_input = CaseStatusInput { case_num };
+/// Returns the proxy reqwest picks up from the environment, if one is set
+fn configured_proxy() -> Option<String> {
+    ["HTTPS_PROXY", "https_proxy", "HTTP_PROXY", "http_proxy", "ALL_PROXY", "all_proxy"]
+        .iter()
+        .find_map(|name| env::var(name).ok())
+        .filter(|proxy| !proxy.trim().is_empty())
+}
 
-    // Prepare the headers.
-    let mut headers = HeaderMap::new();
-    headers.insert("X-API-Key", HeaderValue::from_str(&api_key)?);
-    headers.insert(AUTHORIZATION, HeaderValue::from_str(&basic_auth)?);
-    headers.insert(
-        CONTENT_TYPE,
-        HeaderValue::from_static("application/json; charset=utf-8"),
-    );
+/// Explains why a request never got a response, calling out proxy and TLS problems.
+/// TLS problems are only named deep in the error's source chain, so look for them there.
+pub fn connection_error_message(e: &reqwest::Error) -> String {
+    let mut source = e.source();
+
+    while let Some(cause) = source {
+        let cause_message = cause.to_string();
+        let lowercase = cause_message.to_lowercase();
+
+        if lowercase.contains("certificate")
+            || lowercase.contains("tls")
+            || lowercase.contains("ssl")
+        {
+            return format!(
+                "TLS handshake failed (set EPICOR_CA_CERT if Epicor uses a private CA): {}",
+                cause_message
+            );
+        }
 
-    // Construct the URL
-    let url = format!("{}/api/v2/efx/100/Omni/GetCaseStatus", base_url);
+        source = cause.source();
+    }
 
-    // Send the request and get the response.
-    let resp: Response = client
-        .post(&url)
-        .headers(headers)
-        .json(&complete_task_input)
-        .send()
-        .await?;
+    if e.is_timeout() {
+        format!("timed out: {}", e)
+    } else if e.is_connect() {
+        match configured_proxy() {
+            Some(proxy) => format!(
+                "could not connect through the proxy {} (check HTTPS_PROXY, HTTP_PROXY and NO_PROXY): {}",
+                proxy, e
+            ),
+            None => format!("could not connect: {}", e),
+        }
+    } else {
+        e.to_string()
+    }
+}
 
-    // Check to see if the response was successful.
-    if !resp.status().is_success() {
+/// Turns an unsuccessful Epicor response into an error message that says what to do about it
+async fn status_error(resp: Response) -> anyhow::Error {
+    let status = resp.status();
+    let body = resp.text().await.unwrap_or_default();
+
+    // Prefer the message from Epicor's error payload, e.g. the BPM or validation error
+    let epicor_message = serde_json::from_str::<EpicorError>(&body)
+        .ok()
+        .and_then(|epicor_error| epicor_error.message());
+
+    match status.as_u16() {
+        // Epicor puts the details of a validation error in the body
+        400 => match epicor_message {
+            Some(message) => anyhow!("Epicor rejected the request ({}): {}", status, message),
+            None if !body.trim().is_empty() => {
+                anyhow!("Epicor rejected the request ({}): {}", status, body.trim())
+            }
+            None => anyhow!("Epicor returned {}", status),
+        },
+        401 => anyhow!(
+            "Epicor returned {}. Check EPICOR_API_KEY / basic auth credentials",
+            status
+        ),
+        403 => anyhow!(
+            "Epicor returned {}. Your Epicor account lacks permission for this function",
+            status
+        ),
         // if the error is 404, this means that the function library is likely not published
-        if resp.status().as_u16() == 404 {
-            return Err(anyhow!(
-                "Error: {}",
-                "The Omni function library is not published in Epicor. Please publish the function library and try again."
-            ));
+        404 => anyhow!(
+            "The Omni function library is not published in Epicor. Please publish the function library and try again."
+        ),
+        _ => match epicor_message {
+            Some(message) => anyhow!("Epicor returned {}: {}", status, message),
+            None => anyhow!("Epicor returned {}", status),
+        },
+    }
+}
+
+/// The settings for reaching Epicor's function library, loaded once per run. Flags and the
+/// active profile are applied to the environment before it's read.
+pub struct EpicorConfig {
+    pub base_url: String,
+    pub api_key: String,
+    pub basic_auth: String,
+    pub company: String,
+    pub api_version: String,
+}
+
+impl EpicorConfig {
+    /// Reads and validates the settings, listing every missing one in a single error.
+    /// Credentials aren't needed on a dry run, since nothing is sent.
+    pub fn from_env() -> Result<Self> {
+        let dry_run = DRY_RUN.load(Ordering::Relaxed);
+
+        let base_url = env::var("EPICOR_BASE_URL").ok();
+        let api_key = secret("EPICOR_API_KEY");
+        let basic_auth = secret("EPICOR_BASIC_AUTH");
+
+        let missing: Vec<&str> = [
+            ("EPICOR_BASE_URL", base_url.is_none()),
+            ("EPICOR_API_KEY", api_key.is_none() && !dry_run),
+            ("EPICOR_BASIC_AUTH", basic_auth.is_none() && !dry_run),
+        ]
+        .into_iter()
+        .filter(|(_, is_missing)| *is_missing)
+        .map(|(name, _)| name)
+        .collect();
+
+        if !missing.is_empty() {
+            return Err(missing_settings_error(&missing));
         }
-        return Err(anyhow!("Error: {}", resp.status()));
+
+        Ok(Self {
+            base_url: normalize_base_url(&base_url.unwrap_or_default())?,
+            api_key: api_key.unwrap_or_default(),
+            basic_auth: basic_auth.unwrap_or_default(),
+            company: company()?,
+            api_version: api_version()?,
+        })
     }
+}
 
-    // Deserialize the response.
-    let case_status_response: CaseStatusResponse = resp.json().await?;
+/// Everything needed to call Epicor: its settings and the HTTP client to send requests with.
+/// Commands take it as a parameter so tests can point them at a mock server.
+pub struct EpicorClient {
+    config: EpicorConfig,
+    http: Client,
+}
 
-    // Check for errors.
-    if case_status_response.error {
-        return Err(anyhow!("Error: {}", case_status_response.message));
+impl EpicorClient {
+    pub fn new(config: EpicorConfig, http: Client) -> Self {
+        Self { config, http }
     }
 
-    print_case_status(&case_num, case_status_response);
+    /// Loads the settings from the environment and builds the HTTP client with them
+    pub fn from_env() -> Result<Self> {
+        let config = EpicorConfig::from_env()?;
 
-    Ok(())
-}
+        let http = if DRY_RUN.load(Ordering::Relaxed) {
+            Client::new()
+        } else {
+            http_client(&config)?.clone()
+        };
 
-fn print_case_status(case_num: &u32, case_status_response: CaseStatusResponse) {
-    // Case Num
-    println!("{} {}", "Case Number:".red().bold().underline(), case_num);
-    // Case Owner
-    println!(
-        "{} {}",
-        "Case Owner:".red().bold().underline(),
-        case_status_response.case_owner
-    );
-    // Case Contact
-    println!(
-        "{} {}",
-        "Case Contact:".red().bold().underline(),
-        case_status_response.case_contact
-    );
-    // Internal Contact
-    println!(
-        "{} {}",
-        "Internal Contact:".red().bold().underline(),
-        case_status_response.internal_contact
-    );
-    // Case Description
-    println!(
-        "{} {}",
-        "Case Description:".red().bold().underline(),
-        case_status_response.case_description
-    );
-    // Project
-    println!(
-        "{} {}",
-        "Project:".red().bold().underline(),
-        case_status_response.project_id
-    );
-    // Part Num
-    println!(
-        "{} {}",
-        "Part Num:".red().bold().underline(),
-        case_status_response.part_num
-    );
-    // Unit Price
-    println!(
-        "{} {}",
-        "Unit Price:".red().bold().underline(),
-        case_status_response.unit_price
-    );
-    // Quantity
-    println!(
-        "{} {}",
-        "Quantity:".red().bold().underline(),
-        case_status_response.qty
-    );
-    // Phase
-    println!(
-        "{} {}",
-        "Phase:".red().bold().underline(),
-        case_status_response.wbs_phase_id
-    );
-    // Op
-    println!(
-        "{} {}",
-        "Op:".red().bold().underline(),
-        case_status_response.wbs_phase_op
-    );
-    // CurrentTask
-    println!(
-        "{} {}",
-        "Current Task:".red().bold().underline(),
-        case_status_response.current_task
-    );
-    // AssignedTo
-    println!(
-        "{} {}",
-        "Assigned To:".red().bold().underline(),
-        case_status_response.current_task_assigned_to
-    );
-    // Case Developer
-    println!(
-        "{} {}",
-        "Case Developer:".red().bold().underline(),
-        case_status_response.developer
-    );
-    // Request Date
-    println!(
-        "{} {}",
-        "Request Date:".red().bold().underline(),
-        case_status_response.requested_delivery
-    );
-    // Start Date
-    println!(
-        "{} {}",
-        "Start Date:".red().bold().underline(),
-        case_status_response.start_date
-    );
-    // Expected Delivery Date
-    println!(
-        "{} {}",
-        "Expected Delivery Date:".red().bold().underline(),
-        case_status_response.expected_delivery_date
-    );
-    // Estimated Hours
-    println!(
-        "{} {}",
-        "Estimated Hours:".red().bold().underline(),
-        case_status_response.estimated_hours
-    );
-    // Hours Scheduled
-    println!(
-        "{} {}",
-        "Hours Scheduled:".red().bold().underline(),
-        case_status_response.hours_scheduled
-    );
-    // Hours Applied
-    println!(
-        "{} {}",
-        "Hours Applied:".red().bold().underline(),
-        case_status_response.hours_applied
-    );
-    // Billed Percent
-    println!(
-        "{} {}",
-        "Billed Percent:".red().bold().underline(),
-        case_status_response.billed_percent
-    );
+        Ok(Self::new(config, http))
+    }
 }
 
-pub async fn update_case_quote(case_num: u32, new_quantity: f32) -> Result<()> {
-    // Retrieve environment variables
-    let api_key = env::var("EPICOR_API_KEY").map_err(|_| anyhow!("EPICOR_API_KEY must be set"))?;
-    let basic_auth =
-        env::var("EPICOR_BASIC_AUTH").map_err(|_| anyhow!("EPICOR_BASIC_AUTH must be set"))?;
-    let base_url =
-        env::var("EPICOR_BASE_URL").map_err(|_| anyhow!("EPICOR_BASE_URL must be set"))?;
+/// A request to an Epicor function, built separately from sending it so it can be checked
+/// without a server
+#[derive(Debug)]
+pub struct EpicorRequest {
+    pub url: String,
+    pub body: serde_json::Value,
+}
 
-    // Prepare the HTTP client.
-    let client = Client::new();
+/// Builds the URL and JSON body for calling an Epicor function, e.g. Omni/GetCaseStatus
+pub fn build_request<R: Serialize>(
+    api_endpoint: &str,
+    req_body: &R,
+    config: &EpicorConfig,
+) -> Result<EpicorRequest> {
+    // e.g. <base url>/api/v2/efx/100/Omni/GetCaseStatus
+    let url = format!(
+        "{}/api/{}/efx/{}/{}",
+        config.base_url, config.api_version, config.company, api_endpoint
+    );
 
-    // Prepare the JSON payload.
-    let update_quote_input = UpdateQuoteInput {
-        case_num,
-        new_quantity,
-    };
+    Ok(EpicorRequest {
+        url,
+        body: serde_json::to_value(req_body)?,
+    })
+}
 
-    // Prepare the headers.
-    let mut headers = HeaderMap::new();
-    headers.insert("X-API-Key", HeaderValue::from_str(&api_key)?);
-    headers.insert(AUTHORIZATION, HeaderValue::from_str(&basic_auth)?);
-    headers.insert(
-        CONTENT_TYPE,
-        HeaderValue::from_static("application/json; charset=utf-8"),
-    );
+/// Sends the request to Epicor and returns the deserialized response.
+/// On a dry run the request is printed instead of sent, and None is returned.
+async fn send_request<R: Serialize, S: EpicorResponse + DeserializeOwned + Debug>(
+    client: &EpicorClient,
+    req_body: &R,
+    api_endpoint: &str,
+) -> Result<Option<S>> {
+    let request = build_request(api_endpoint, req_body, &client.config)?;
+
+    // The dry run goes to stderr like other status output, and isn't hidden by --quiet since
+    // showing the request is the point
+    if DRY_RUN.load(Ordering::Relaxed) {
+        eprintln!("{} POST {}", "Dry Run:".yellow().bold(), request.url);
+        eprintln!("{}", serde_json::to_string_pretty(&request.body)?);
+        return Ok(None);
+    }
 
-    // Construct the URL
-    let url = format!("{}/api/v2/efx/100/Omni/UpdateCaseQuote", base_url);
+    execute(client, &request).await.map(Some)
+}
 
+/// Sends a built request and checks both the HTTP status and the Error flag in the response
+async fn execute<S: EpicorResponse + DeserializeOwned + Debug>(
+    client: &EpicorClient,
+    request: &EpicorRequest,
+) -> Result<S> {
     // Send the request and get the response.
-    let resp: Response = client
-        .post(&url)
-        .headers(headers)
-        .json(&update_quote_input)
-        .send()
-        .await?;
+    let spinner = Spinner::start("Contacting Epicor…");
+    let resp = post_with_retry(&client.http, &request.url, &request.body).await?;
+    drop(spinner);
 
     // Check to see if the response was successful.
     if !resp.status().is_success() {
-        // if the error is 404, this means that the function library is likely not published
-        if resp.status().as_u16() == 404 {
-            return Err(anyhow!(
-                "Error: {}",
-                "The Omni function library is not published in Epicor. Please publish the function library and try again."
-            ));
-        }
-        return Err(anyhow!("Error: {}", resp.status()));
+        return Err(status_error(resp).await);
     }
 
-    // Deserialize the response.
-    let update_quote_response: UpdateQuoteResponse = resp.json().await?;
+    // Deserialize the response as the type requested by the caller.
+    let api_response: S = resp.json().await?;
+
+    trace!("api_response: {:?}", api_response);
 
     // Check for errors.
-    if update_quote_response.error {
-        return Err(anyhow!("Error: {}", update_quote_response.message));
+    if let Some(message) = api_response.error_message() {
+        return Err(anyhow!(message));
     }
 
-    println!(
-        "{}",
-        "Quote Updated and Attached to Case".bright_green().bold(),
-    );
-
-    Ok(())
+    Ok(api_response)
 }
 
-pub async fn add_case_comment(case_num: u32, comment: &str) -> Result<()> {
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::TempDir;
+    use wiremock::matchers::{body_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn test_config() -> EpicorConfig {
+        EpicorConfig {
+            base_url: "https://epicor.example.com/ERP".to_string(),
+            api_key: "test-key".to_string(),
+            basic_auth: "Basic dGVzdA==".to_string(),
+            api_version: "v2".to_string(),
+            company: "100".to_string(),
+        }
+    }
+
+    #[test]
+    fn complete_task_targets_the_complete_task_function() {
+        let input = CompleteTaskInput::new(1234, Some("Jane Doe"));
 
-    let add_comment_input = AddCaseCommentInput {
-        case_num,
-        comment: comment.to_string()
-    };
+        let request = build_request("Omni/CompleteTask", &input, &test_config()).unwrap();
 
-    let _result = send_request::<AddCaseCommentInput, AddCaseCommentResponse>(Some(RequestBodyType::AddCaseCommentBody(add_comment_input)), "efx/100/Omni/AddCaseComment").await?;
+        assert_eq!(request.url, "https://epicor.example.com/ERP/api/v2/efx/100/Omni/CompleteTask");
+        assert_eq!(request.body, json!({ "CaseNum": 1234, "AssignNextToName": "Jane Doe" }));
+    }
 
-    println!(
-        "{}",
-        "Comment Added to Case".bright_green().bold(),
-    );
+    #[test]
+    fn request_url_uses_the_api_version_and_company() {
+        let config = EpicorConfig {
+            api_version: "v3".to_string(),
+            company: "EPIC06".to_string(),
+            ..test_config()
+        };
+
+        let request =
+            build_request("Omni/GetCaseStatus", &CaseStatusInput::new(1), &config).unwrap();
+
+        assert_eq!(
+            request.url,
+            "https://epicor.example.com/ERP/api/v3/efx/EPIC06/Omni/GetCaseStatus"
+        );
+    }
 
-    Ok(())
-}
+    #[test]
+    fn time_entry_sends_the_labor_type_by_name_and_expense_code_by_number() {
+        let time_entry = TimeEntry::new(
+            42,
+            LaborType::Project,
+            Some("PRJ-001".to_string()),
+            None,
+            None,
+            Some("MfgSys".to_string()),
+            Some(ExpenseCode::DirectLabor),
+            1.5,
+        );
+
+        let body = serde_json::to_value(AddTimeEntryInput::from(time_entry)).unwrap();
+
+        assert_eq!(body["LaborType"], "Project");
+        assert_eq!(body["ExpenseCode"], 1);
+
+        let expense_code: ExpenseCode = serde_json::from_value(json!(2)).unwrap();
+        assert_eq!(expense_code, ExpenseCode::IndirectLabor);
+        assert_eq!("direct-labor".parse::<ExpenseCode>().unwrap(), ExpenseCode::DirectLabor);
+    }
 
-pub async fn get_last_case_comment(case_num: u32) -> Result<()> {
-    // Retrieve environment variables
-    let api_key = env::var("EPICOR_API_KEY").map_err(|_| anyhow!("EPICOR_API_KEY must be set"))?;
-    let basic_auth =
-        env::var("EPICOR_BASIC_AUTH").map_err(|_| anyhow!("EPICOR_BASIC_AUTH must be set"))?;
-    let base_url =
-        env::var("EPICOR_BASE_URL").map_err(|_| anyhow!("EPICOR_BASE_URL must be set"))?;
+    #[test]
+    fn complete_task_refusals_have_their_own_exit_codes() {
+        let response: CompleteTaskResponse = serde_json::from_value(json!({
+            "Error": true,
+            "Message": "Not authorized",
+            "HasActiveTask": true,
+            "AuthorizedToCompleteTask": false,
+            "MultipleSalesRepMatches": false,
+            "NoSalesRepMatch": false
+        }))
+        .unwrap();
+
+        let refusal = response.refusal().unwrap();
+
+        assert_eq!(refusal.exit_code(), 4);
+        assert!(refusal.to_string().starts_with("You are not authorized"));
+        assert_eq!(response.error_message(), None);
+    }
 
-    // Prepare the HTTP client.
-    let client = Client::new();
+    #[test]
+    fn an_error_without_a_message_is_reported_as_unknown() {
+        let response: UpdateQuoteResponse =
+            serde_json::from_value(json!({ "Error": true, "Message": " " })).unwrap();
 
-    // Prepare the JSON payload.
-    let last_case_comment_input = GetLastCommentInput {
-        case_num
-    };
+        assert_eq!(response.error_message().as_deref(), Some("Unknown Error"));
 
-    // Prepare the headers.
-    let mut headers = HeaderMap::new();
-    headers.insert("X-API-Key", HeaderValue::from_str(&api_key)?);
-    headers.insert(AUTHORIZATION, HeaderValue::from_str(&basic_auth)?);
-    headers.insert(
-        CONTENT_TYPE,
-        HeaderValue::from_static("application/json; charset=utf-8"),
-    );
+        let response: UpdateCaseResponse =
+            serde_json::from_value(json!({ "Error": false })).unwrap();
 
-    // Construct the URL
-    let url = format!("{}/api/v2/efx/100/Omni/GetLastComment", base_url);
+        assert_eq!(response.error_message(), None);
+    }
 
-    // Send the request and get the response.
-    let resp: Response = client
-        .post(&url)
-        .headers(headers)
-        .json(&last_case_comment_input)
-        .send()
-        .await?;
+    #[test]
+    fn a_blank_message_is_reported_as_unknown_by_every_response() {
+        let response: CloseCaseResponse =
+            serde_json::from_value(json!({ "Error": true, "Message": "" })).unwrap();
 
-    // Check to see if the response was successful.
-    if !resp.status().is_success() {
-        // if the error is 404, this means that the function library is likely not published
-        if resp.status().as_u16() == 404 {
-            return Err(anyhow!(
-                "Error: {}",
-                "The Omni function library is not published in Epicor. Please publish the function library and try again."
-            ));
-        }
-        return Err(anyhow!("Error: {}", resp.status()));
+        assert_eq!(response.error_message().as_deref(), Some("Unknown Error"));
+
+        let response: ReassignResponse = serde_json::from_value(json!({
+            "Error": true,
+            "Message": " ",
+            "MultipleSalesRepMatches": false,
+            "NoSalesRepMatch": false
+        }))
+        .unwrap();
+
+        assert_eq!(response.error_message().as_deref(), Some("Unknown Error"));
+
+        let response: CloseCaseResponse =
+            serde_json::from_value(json!({ "Error": true, "HasOpenTask": true })).unwrap();
+
+        assert_eq!(response.error_message(), None);
     }
 
-    // Deserialize the response.
-    let last_comment_response: GetLastCommentResponse = resp.json().await?;
+    #[test]
+    fn a_completed_task_is_not_a_refusal() {
+        let response: CompleteTaskResponse = serde_json::from_value(json!({
+            "Error": false,
+            "Message": "",
+            "HasActiveTask": false,
+            "AuthorizedToCompleteTask": false,
+            "MultipleSalesRepMatches": false,
+            "NoSalesRepMatch": false
+        }))
+        .unwrap();
+
+        assert!(response.refusal().is_none());
+        assert_eq!(response.error_message(), None);
+    }
 
-    // Check for errors.
-    if last_comment_response.error {
-        return Err(anyhow!("Error: {}", last_comment_response.message.unwrap_or("Unknown Error".to_string())));
+    #[test]
+    fn update_case_only_sends_the_provided_fields() {
+        let input = UpdateCaseInput::new(1234, Some(5.0), None, None, None, None);
+
+        let request = build_request("Omni/UpdateCase", &input, &test_config()).unwrap();
+
+        assert_eq!(request.body, json!({ "CaseNum": 1234, "Qty": 5.0 }));
     }
 
-    println!("{}", "Last Comment".bright_green().bold().underline());
+    #[test]
+    fn update_case_sends_delivery_dates_as_timestamps() {
+        let expected = NaiveDate::from_ymd_opt(2023, 6, 15);
+        let input = UpdateCaseInput::new(1234, None, None, None, None, expected);
 
-    println!(
-        "{}",
-        last_comment_response.comment.unwrap_or("No comments".to_string()).bright_red(),
-    );
+        let request = build_request("Omni/UpdateCase", &input, &test_config()).unwrap();
 
-    Ok(())
-}
+        assert_eq!(
+            request.body,
+            json!({ "CaseNum": 1234, "ExpectedDeliveryDate": "2023-06-15T00:00:00" })
+        );
+    }
 
-async fn send_request<R: Serialize, S: for<'de> Deserialize<'de>>(
-    req_body: Option<RequestBodyType>,
-    api_endpoint: &str,
-) -> Result<()> {
-    // Retrieve environment variables
-    let api_key = env::var("EPICOR_API_KEY")?;
-    let basic_auth = env::var("EPICOR_BASIC_AUTH")?;
-    let base_url = env::var("EPICOR_BASE_URL")?;
+    #[test]
+    fn set_case_dates_sends_only_the_provided_dates_as_timestamps() {
+        let start = NaiveDate::from_ymd_opt(2023, 6, 1);
+        let input = SetCaseDatesInput::new(1234, None, start, None);
 
-    // Prepare the HTTP client.
-    let client = Client::new();
+        let request = build_request("Omni/SetCaseDates", &input, &test_config()).unwrap();
 
-    // Prepare the JSON payload.
-    let body = match req_body {
-        Some(RequestBodyType::UpdateQuoteBody(update_quote_input)) => {
-            serde_json::to_value(update_quote_input)?
-        }
-        Some(RequestBodyType::AddCaseCommentBody(add_comment_input)) => {
-            serde_json::to_value(add_comment_input)?
+        assert_eq!(request.body, json!({ "CaseNum": 1234, "StartDate": "2023-06-01T00:00:00" }));
+        assert_eq!(parse_epicor_date(Some("2023-06-01T00:00:00")), start);
+        assert_eq!(parse_epicor_date(Some("0001-01-01T00:00:00")), None);
+    }
+
+    #[test]
+    fn default_headers_carry_the_credentials_but_logs_do_not() {
+        let headers = default_headers("secret-key", "Basic c2VjcmV0").unwrap();
+
+        assert_eq!(headers["x-api-key"], "secret-key");
+        assert_eq!(headers[AUTHORIZATION], "Basic c2VjcmV0");
+        assert_eq!(headers[CONTENT_TYPE], "application/json; charset=utf-8");
+
+        let logged = format!("{:?}", redacted_headers(&headers));
+        assert!(!logged.contains("secret"));
+        assert!(!logged.contains("c2VjcmV0"));
+    }
 
+    #[test]
+    fn base_url_drops_trailing_slashes_and_api_version() {
+        for base_url in [
+            "https://epicor.example.com/ERP",
+            "https://epicor.example.com/ERP/",
+            "https://epicor.example.com/ERP/api/v2",
+            " https://epicor.example.com/ERP/api/V1/ ",
+        ] {
+            assert_eq!(
+                normalize_base_url(base_url).unwrap(),
+                "https://epicor.example.com/ERP"
+            );
         }
-        Some(RequestBodyType::CaseStatusBody(case_status_input)) => {
-            serde_json::to_value(case_status_input)?
+
+        assert!(normalize_base_url("epicor.example.com/ERP").is_err());
+        assert!(normalize_base_url("ftp://epicor.example.com/ERP").is_err());
+    }
+
+    #[test]
+    fn case_status_defaults_the_columns_epicor_leaves_out() {
+        let mut body = case_status_body();
+        let columns = body.as_object_mut().unwrap();
+
+        for column in ["Message", "InternalContact", "CaseContact", "Developer", "StartDate"] {
+            columns.remove(column);
         }
-        Some(RequestBodyType::CompleteTaskBody(update_case_quote_input)) => {
-            serde_json::to_value(update_case_quote_input)?
+
+        let case_status: CaseStatusResponse = serde_json::from_value(body).unwrap();
+
+        assert_eq!(case_status.developer, "");
+        assert_eq!(case_status.start_date, None);
+        assert_eq!(case_status.current_task, "Engineering Review");
+    }
+
+    #[test]
+    fn comments_response_without_comments_is_empty() {
+        for body in [json!({ "Error": false }), json!({ "Error": false, "Comments": null })] {
+            let comments_response: GetCommentsResponse = serde_json::from_value(body).unwrap();
+
+            assert!(comments_response.comments.is_empty());
         }
-        Some(RequestBodyType::GetLastCommentBody(get_last_comment_input)) => {
-            serde_json::to_value(get_last_comment_input)?
+    }
+
+    #[test]
+    fn list_cases_response_without_cases_is_empty() {
+        for body in [json!({ "Error": false }), json!({ "Error": false, "Cases": null })] {
+            let list_cases_response: ListCasesResponse = serde_json::from_value(body).unwrap();
+
+            assert!(list_cases_response.cases.is_empty());
         }
-        // Handle other types...
-        _ => return Err(anyhow!("Unsupported request body type")),
-    };
+    }
 
-    // Prepare the headers.
-    let mut headers = reqwest::header::HeaderMap::new();
-    headers.insert(
-        "X-API-Key",
-        reqwest::header::HeaderValue::from_str(&api_key)?,
-    );
-    headers.insert(
-        reqwest::header::AUTHORIZATION,
-        reqwest::header::HeaderValue::from_str(&basic_auth)?,
-    );
-    headers.insert(
-        reqwest::header::CONTENT_TYPE,
-        reqwest::header::HeaderValue::from_static("application/json; charset=utf-8"),
-    );
+    #[test]
+    fn retry_delay_doubles_up_to_the_cap() {
+        assert_eq!(retry_delay(0), Duration::from_millis(500));
+        assert_eq!(retry_delay(2), Duration::from_secs(2));
+        assert_eq!(retry_delay(60), Duration::from_millis(MAX_RETRY_DELAY_MS));
+        assert_eq!(retry_delay(u32::MAX), Duration::from_millis(MAX_RETRY_DELAY_MS));
+    }
 
-    // Construct the URL
-    let url = format!("{}/api/v2/{}", base_url, api_endpoint);
+    /// A client for a mock Epicor server, with the function library under /ERP. The case status
+    /// cache is kept in a temporary directory so a developer's own cache is never touched.
+    fn mock_client(server: &MockServer) -> EpicorClient {
+        static CACHE_DIR: OnceLock<TempDir> = OnceLock::new();
 
-    // Send the request and get the response.
-    let resp = client
-        .post(&url)
-        .headers(headers)
-        .json(&body)
-        .send()
-        .await?;
+        CACHE_DIR.get_or_init(|| {
+            let cache_dir = TempDir::new().unwrap();
+            env::set_var("OMNI_CACHE_DIR", cache_dir.path());
 
-    // Check to see if the response was successful.
-    if !resp.status().is_success() {
-        // if the error is 404, this means that the function library is likely not published
-        if resp.status().as_u16() == 404 {
-            return Err(anyhow!("The Omni function library is not published in Epicor. Please publish the function library and try again."));
-        }
-        return Err(anyhow!(format!("Error: {}", resp.status())));
+            cache_dir
+        });
+
+        let config = EpicorConfig {
+            base_url: format!("{}/ERP", server.uri()),
+            ..test_config()
+        };
+
+        EpicorClient::new(config, Client::new())
     }
 
-    // Deserialize the response. Make sure it is deserialized as the type passed in by the user.
-    let api_response = serde_json::from_str::<ApiResponse>(&resp.text().await?)?;
+    fn case_status_body() -> serde_json::Value {
+        serde_json::from_str(include_str!("../tests/fixtures/case_status.json")).unwrap()
+    }
 
-    // print response
-    println!("api_response: {:?}", api_response);
+    #[tokio::test]
+    async fn get_case_status_posts_the_case_number() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/ERP/api/v2/efx/100/Omni/GetCaseStatus"))
+            .and(body_json(json!({ "CaseNum": 1234 })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(case_status_body()))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let case_status = fetch_case_status(&mock_client(&server), 1234)
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(case_status.current_task, "Engineering Review");
+        assert_eq!(case_status.start_date, None);
+    }
 
-    match api_response {
-        ApiResponse::UpdateQuoteBody(update_quote_response) => {
-            // Check for errors.
-            if update_quote_response.error {
-                return Err(anyhow!(format!("Error: {}", update_quote_response.message)));
-            }
-        }
-        ApiResponse::AddCaseCommentBody(add_comment_response) => {
-            // Check for errors. Note that response.message can be null and is optional
-            if add_comment_response.error {
-                return Err(anyhow!(format!("Error: {}", add_comment_response.message.unwrap_or("".to_string()))));
-            }
-        }
-        ApiResponse::CaseStatusBody(case_status_response) => {
-            // Check for errors.
-            if case_status_response.error {
-                return Err(anyhow!(format!("Error: {}", case_status_response.message)));
-            }
-        }
-        ApiResponse::CompleteTaskBody(complete_task_response) => {
-            // Check for errors.
-            if complete_task_response.error {
-                return Err(anyhow!(format!("Error: {}", complete_task_response.message)));
-            }
-        }
-        ApiResponse::GetLastCommentBody(get_last_comment_response) => {
+    #[tokio::test]
+    async fn update_case_quote_sends_the_new_quantity() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/ERP/api/v2/efx/100/Omni/UpdateCaseQuote"))
+            .and(body_json(json!({ "CaseNum": 1234, "Qty": 3.0 })))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(json!({ "Error": false, "Message": "" })),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        update_case_quote(&mock_client(&server), 1234, 3.0, false)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn add_case_comment_fails_when_epicor_reports_an_error() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/ERP/api/v2/efx/100/Omni/AddCaseComment"))
+            .and(body_json(json!({ "CaseNum": 1234, "Comment": "Parts ordered" })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "Error": true,
+                "Message": "Case 1234 is closed"
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let error = add_case_comment(&mock_client(&server), 1234, "Parts ordered", false)
+            .await
+            .unwrap_err();
+
+        assert_eq!(error.to_string(), "Case 1234 is closed");
+    }
 
-            println!("get_last_comment_response: {:?}", get_last_comment_response);
+    #[tokio::test]
+    async fn send_complete_task_reports_an_unpublished_library() {
+        let server = MockServer::start().await;
 
-            // Check for errors.
-            if get_last_comment_response.error {
-                return Err(anyhow!(format!("Error: {}", get_last_comment_response.message.unwrap_or("".to_string()))));
-            }
+        Mock::given(method("POST"))
+            .and(path("/ERP/api/v2/efx/100/Omni/CompleteTask"))
+            .respond_with(ResponseTemplate::new(404))
+            .expect(1)
+            .mount(&server)
+            .await;
 
-            // print the comment
-            println!("Last Comment: {}", get_last_comment_response.comment.unwrap_or("".to_string()));
-        }
+        let error =
+            send_complete_task(&mock_client(&server), 1234, Some("Jane Doe"), Some("Done"), false)
+                .await
+                .unwrap_err();
+
+        assert!(error.to_string().contains("function library is not published"));
     }
 
+    #[tokio::test]
+    async fn set_estimated_hours_rejects_negative_hours_without_a_request() {
+        let server = MockServer::start().await;
 
+        let result = set_estimated_hours(&mock_client(&server), 1234, -1.0).await;
 
-    Ok(())
+        assert!(result.is_err());
+        assert!(server.received_requests().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn complete_task_closes_the_case_after_its_final_task() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/ERP/api/v2/efx/100/Omni/CompleteTask"))
+            .and(body_json(json!({ "CaseNum": 1234 })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "Error": false,
+                "Message": "",
+                "HasActiveTask": true,
+                "AuthorizedToCompleteTask": true,
+                "MultipleSalesRepMatches": false,
+                "NoSalesRepMatch": false,
+                "IsFinalTask": true
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        Mock::given(method("POST"))
+            .and(path("/ERP/api/v2/efx/100/Omni/CloseCase"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(json!({ "Error": false, "Message": "" })),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        send_complete_task(&mock_client(&server), 1234, None, None, true)
+            .await
+            .unwrap();
+    }
 }
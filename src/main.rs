@@ -1,75 +1,487 @@
 mod args;
 mod bitwarden;
+mod config;
+mod doctor;
 mod epicor;
+mod openai;
+mod output;
 mod setup;
 
 use crate::args::{
-    BitwardenSubcommand, CaseSubcommand, EntityType, EpicorCommand, EpicorSubcommand,
+    BitwardenSubcommand, CaseSubcommand, ConfigSubcommand, EntityType,
+    EpicorSubcommand, TimeEntrySubcommand,
 };
-use crate::bitwarden::{get_item, list_items};
-use crate::epicor::{add_case_comment, get_case_status, get_last_case_comment, send_complete_task, update_case_quote};
-use crate::setup::setup;
+use crate::bitwarden::{
+    config_server, create_item, delete_item, edit_item, end_session, generate, get_attachment,
+    get_item, list_items, load_epicor_credentials, run_raw, sync_vault, vault_status,
+};
+use crate::config::{
+    default_assignee, default_owner, default_plant, env_file_not_found_message,
+    load_config_override, load_env_file, load_profile, resolve_assignee, set_config_override,
+    set_config_value, show_config, ConfigFormat,
+};
+use crate::doctor::doctor;
+use crate::epicor::{
+    add_case_attachment, add_case_comment, close_case, complete_task_batch, confirm_complete_task,
+    confirm_complete_task_batch, create_case, edit_last_comment, get_case_comments, get_case_status,
+    get_case_statuses, get_last_case_comment, list_cases, list_time_entries, move_case_phase,
+    open_case_url, ping, read_case_batch, reassign_case, send_complete_task, send_time_entry,
+    set_accept_invalid_certs, set_cache_ttl, set_case_dates, set_date_format, set_dry_run,
+    set_estimated_hours, update_case, update_case_quote, watch_case_status, CloseCaseInput,
+    CompleteTaskError, CreateCaseInput, EpicorClient, ListCasesInput, ListFormat, TimeEntry,
+    UpdateCaseInput,
+};
+use crate::output::{set_compact_json, set_output_file, set_quiet, status_println};
+use crate::setup::{read_secret_value, resolve_secret_sources, run_setup_wizard, setup};
 use anyhow::{anyhow, Result};
 use args::OmniArgs;
-use clap::{arg, command, Command as ClapCommand, Parser, Subcommand};
-use dotenv::dotenv;
-use figlet_rs::FIGfont;
-use regex::Regex;
+use clap::Parser;
+use colored::Colorize;
 use std::env;
-use std::process::Command;
+use std::io::{self, IsTerminal};
+use std::process;
+use tracing::Level;
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::fmt;
+use tracing_subscriber::prelude::*;
 
 #[tokio::main]
-async fn main() -> Result<()> {
-    dotenv().ok();
+async fn main() {
     let args = OmniArgs::parse();
+    let json = args.json;
+
+    // Report failures in a friendly way, and with a nonzero exit code so scripts can detect them.
+    // Under --json the error is an object too, so automation doesn't have to match on the text.
+    if let Err(e) = run(args).await {
+        if json {
+            eprintln!("{}", serde_json::json!({ "error": true, "message": e.to_string() }));
+        } else {
+            eprintln!("{} {}", "Error:".red().bold(), e);
+        }
+
+        // Refusals to complete a task get their own exit codes, everything else exits with 1
+        let exit_code = e
+            .downcast_ref::<CompleteTaskError>()
+            .map_or(1, |complete_task_error| complete_task_error.exit_code());
+
+        process::exit(exit_code);
+    }
+}
+
+/// Each -v shows more detail: requests, then raw responses, then logs from dependencies too
+fn init_logging(verbosity: u8) {
+    let targets = match verbosity {
+        1 => Targets::new().with_target("omni", Level::DEBUG),
+        2 => Targets::new().with_target("omni", Level::TRACE),
+        _ => Targets::new().with_default(Level::TRACE),
+    };
+
+    tracing_subscriber::registry()
+        .with(fmt::layer().with_writer(io::stderr))
+        .with(targets)
+        .init();
+}
+
+async fn run(args: OmniArgs) -> Result<()> {
+    // The output settings come first, so they apply to everything printed from here on
+    set_quiet(args.quiet);
+
+    // Pipelines usually want one JSON document per line, people want it indented
+    set_compact_json(
+        args.compact || (!args.pretty && (args.output.is_some() || !io::stdout().is_terminal())),
+    );
+
+    // Colors only belong on a terminal, can be turned off with NO_COLOR or --no-color,
+    // and would break parsing of JSON output or end up in the --output file
+    if args.json
+        || args.output.is_some()
+        || args.no_color
+        || env::var_os("NO_COLOR").is_some()
+        || !io::stdout().is_terminal()
+    {
+        colored::control::set_override(false);
+    }
+
+    // get-attachment has bw write the attachment to --output itself
+    let saves_attachment = matches!(
+        &args.entity_type,
+        EntityType::Bitwarden(bitwarden)
+            if matches!(bitwarden.subcommand, BitwardenSubcommand::GetAttachment(_))
+    );
+
+    if let Some(output) = args.output.as_ref().filter(|_| !saves_attachment) {
+        set_output_file(output)?;
+    }
+
+    // --config replaces the usual config files, including the ones setup writes
+    if let Some(config) = &args.config {
+        set_config_override(config);
+    }
+
+    // Load the selected config profile, falling back to the .env file written by `omni setup`.
+    // Setup writes the configuration rather than reading it, so it skips this.
+    if !matches!(args.entity_type, EntityType::Setup(_)) {
+        if let Some(config) = &args.config {
+            load_config_override(config, args.profile.as_deref())?;
+        } else if load_profile(args.profile.as_deref())?.is_none() && load_env_file().is_none() {
+            status_println!("{}", env_file_not_found_message());
+        }
+    }
+
+    // Diagnostic output is only shown when asked for, and always on stderr
+    if args.verbose > 0 {
+        init_logging(args.verbose);
+    }
+
+    set_dry_run(args.dry_run);
+    set_accept_invalid_certs(args.danger_accept_invalid_certs);
+    set_cache_ttl(args.cache_ttl);
+
+    if let Some(date_format) = &args.date_format {
+        set_date_format(date_format)?;
+    }
+
+    if let Some(timeout) = args.timeout {
+        env::set_var("EPICOR_TIMEOUT_SECS", timeout.to_string());
+    }
+
+    // Epicor credentials kept in the vault replace the ones from the .env file or keychain.
+    // A dry run sends nothing, so it doesn't need them or the vault.
+    if matches!(args.entity_type, EntityType::Epicor(_)) && !args.dry_run {
+        if let Ok(item_name) = env::var("EPICOR_CREDENTIALS_ITEM") {
+            load_epicor_credentials(&item_name)?;
+        }
+    }
+
+    // Flags take precedence over the .env file, e.g. to target a test environment for one run
+    if let Some(base_url) = &args.base_url {
+        env::set_var("EPICOR_BASE_URL", base_url);
+    }
+
+    if let Some(company) = &args.company {
+        env::set_var("EPICOR_COMPANY", company);
+    }
+
+    if let Some(api_version) = &args.api_version {
+        env::set_var("EPICOR_API_VERSION", api_version);
+    }
+
+    if let Some(api_key) = &args.api_key {
+        env::set_var("EPICOR_API_KEY", api_key);
+    }
+
+    if let Some(basic_auth) = &args.basic_auth {
+        env::set_var("EPICOR_BASIC_AUTH", basic_auth);
+    }
 
     match args.entity_type {
         EntityType::Bitwarden(bitwarden) => match bitwarden.subcommand {
-            BitwardenSubcommand::List => {
-                return list_items();
+            BitwardenSubcommand::List(list) => {
+                return list_items(&list);
             }
             BitwardenSubcommand::Get(get) => {
-                return get_item(&get.item_type, &get.name);
+                return get_item(&get.item_type, &get.name, get.sync, get.copy);
             }
             BitwardenSubcommand::Create(create) => {
-                println!("Create");
+                return create_item(
+                    &create.name,
+                    &create.username,
+                    &create.password,
+                    create.notes.as_deref(),
+                );
+            }
+            BitwardenSubcommand::Delete(delete) => {
+                return delete_item(&delete.id);
+            }
+            BitwardenSubcommand::Edit(edit) => {
+                return edit_item(
+                    &edit.id,
+                    edit.name.as_deref(),
+                    edit.username.as_deref(),
+                    edit.password.as_deref(),
+                    edit.notes.as_deref(),
+                );
+            }
+            BitwardenSubcommand::Sync => {
+                return sync_vault();
+            }
+            BitwardenSubcommand::Logout => {
+                return end_session();
+            }
+            BitwardenSubcommand::Status => {
+                return vault_status(args.json);
+            }
+            BitwardenSubcommand::Generate(options) => {
+                return generate(&options);
+            }
+            BitwardenSubcommand::GetAttachment(attachment) => {
+                let output = args.output.as_deref().ok_or(anyhow!(
+                    "get-attachment needs --output [path] to save the attachment to"
+                ))?;
+                return get_attachment(&attachment.name, &attachment.item_id, output);
+            }
+            BitwardenSubcommand::Raw(raw) => {
+                return run_raw(&raw.args);
+            }
+            BitwardenSubcommand::ConfigServer(server) => {
+                return config_server(&server.url);
             }
         },
-        EntityType::Epicor(epicor) => match epicor.subcommand {
-            EpicorSubcommand::Case(case) => match case.subcommand {
-                CaseSubcommand::CompleteTask(case) => {
-
-                    match send_complete_task(case.case_number.clone(), case.assign_to.as_str()).await {
-                        Ok(_) => println!("Task Completed"),
-                        Err(e) => println!("Error Completing Task: {}", e),
-                    };
-
-                    // Check if the user provided a comment to add to the case
-                    if let Some(comment) = &case.comment {
-                        match add_case_comment(case.case_number.clone(), comment.as_str()).await {
-                            Ok(_) => (),
-                            Err(e) => println!("Error Adding Comment: {}", e),
+        EntityType::Epicor(epicor) => {
+            let client = EpicorClient::from_env()?;
+
+            match epicor.subcommand {
+                EpicorSubcommand::Case(case) => match case.subcommand {
+                    CaseSubcommand::Create(case) => {
+                        let create_case_input = CreateCaseInput::new(
+                            case.customer,
+                            case.description,
+                            case.project,
+                            case.part_num,
+                            case.owner.or(default_owner()?),
+                        );
+                        create_case(&client, create_case_input, args.json).await?;
+                    }
+                    CaseSubcommand::CompleteTask(case) => {
+                        let assign_to = if case.to_me {
+                            Some(default_assignee()?)
+                        } else {
+                            case.assign_to
+                        };
+
+                        if let Some(from_file) = &case.from_file {
+                            let cases = read_case_batch(from_file, assign_to.as_deref())?
+                                .into_iter()
+                                .map(|(case_num, assign_to)| {
+                                    Ok((case_num, resolve_assignee(&assign_to)?))
+                                })
+                                .collect::<Result<Vec<_>>>()?;
+
+                            // Confirm the whole batch once rather than case by case
+                            if !case.yes
+                                && io::stdout().is_terminal()
+                                && !confirm_complete_task_batch(cases.len(), from_file)?
+                            {
+                                status_println!("Tasks not completed");
+                                return Ok(());
+                            }
+
+                            return complete_task_batch(
+                                &client,
+                                &cases,
+                                case.comment.as_deref(),
+                                case.fail_fast,
+                            )
+                            .await;
+                        }
+
+                        // Clap requires the case number unless --from-file is given, and the
+                        // assignee unless --close-if-final is given too
+                        let Some(case_number) = case.case_number else {
+                            return Err(anyhow!("--case-number is required"));
                         };
+
+                        if assign_to.is_none() && !case.close_if_final {
+                            return Err(anyhow!("--assign-to (or --to-me) is required"));
+                        }
+
+                        let assign_to = assign_to.as_deref().map(resolve_assignee).transpose()?;
+
+                        // Completing a task can't be undone, so confirm first unless scripted
+                        // or told not to
+                        if !case.yes
+                            && io::stdout().is_terminal()
+                            && !confirm_complete_task(&client, case_number, assign_to.as_deref())
+                                .await?
+                        {
+                            status_println!("Task not completed");
+                            return Ok(());
+                        }
+
+                        // The optional comment is added as part of completing the task
+                        send_complete_task(
+                            &client,
+                            case_number,
+                            assign_to.as_deref(),
+                            case.comment.as_deref(),
+                            case.close_if_final,
+                        )
+                        .await?;
                     }
+                    CaseSubcommand::GetStatus(case) => {
+                        if let [case_number] = case.case_number[..] {
+                            get_case_status(&client, case_number, args.json).await?;
+                        } else {
+                            get_case_statuses(
+                                &client,
+                                &case.case_number,
+                                case.concurrency,
+                                args.json,
+                                case.fail_fast,
+                            )
+                            .await?;
+                        }
+                    }
+                    CaseSubcommand::GetCommentSummary(case) => {
+                        get_case_comments(
+                            &client,
+                            case.case_number,
+                            args.json,
+                            case.ai,
+                            case.limit,
+                            case.since,
+                        )
+                        .await?;
+                    }
+                    CaseSubcommand::EditComment(case) => {
+                        edit_last_comment(&client, case.case_number, case.comment.as_str()).await?;
+                    }
+                    CaseSubcommand::AddComment(case) => {
+                        add_case_comment(
+                            &client,
+                            case.case_number,
+                            case.comment.as_str(),
+                            case.dedupe,
+                        )
+                        .await?;
+                    }
+                    CaseSubcommand::AddAttachment(case) => {
+                        add_case_attachment(&client, case.case_number, &case.file).await?;
+                    }
+                    CaseSubcommand::UpdateQuote(case) => {
+                        update_case_quote(
+                            &client,
+                            case.case_number,
+                            case.new_quantity,
+                            case.allow_zero,
+                        )
+                        .await?;
+                    }
+                    CaseSubcommand::Update(case) => {
+                        let update_case_input = UpdateCaseInput::new(
+                            case.case_number,
+                            case.qty,
+                            case.unit_price,
+                            case.part_num,
+                            case.requested_delivery,
+                            case.expected_delivery,
+                        );
+                        update_case(&client, update_case_input).await?;
+                    }
+                    CaseSubcommand::SetDates(case) => {
+                        set_case_dates(
+                            &client,
+                            case.case_number,
+                            case.requested,
+                            case.start,
+                            case.expected,
+                        )
+                        .await?;
+                    }
+                    CaseSubcommand::GetLastComment(case) => {
+                        get_last_case_comment(&client, case.case_number).await?;
+                    }
+                    CaseSubcommand::Reassign(case) => {
+                        let assign_to = resolve_assignee(&case.assign_to)?;
+                        reassign_case(&client, case.case_number, assign_to.as_str()).await?;
+                    }
+                    CaseSubcommand::MovePhase(case) => {
+                        move_case_phase(&client, case.case_number, &case.phase, case.op).await?;
+                    }
+                    CaseSubcommand::SetEstimate(case) => {
+                        set_estimated_hours(&client, case.case_number, case.hours).await?;
+                    }
+                    CaseSubcommand::Close(case) => {
+                        let close_case_input = CloseCaseInput::new(
+                            case.case_number,
+                            case.comment.as_deref(),
+                            case.force,
+                        );
+                        close_case(&client, close_case_input).await?;
+                    }
+                    CaseSubcommand::List(list) => {
+                        let list_cases_input = ListCasesInput::new(
+                            list.assigned_to,
+                            list.project,
+                            list.owner,
+                            list.limit,
+                        );
+                        let format = if args.json {
+                            ListFormat::Json
+                        } else {
+                            list.format
+                        };
+                        list_cases(&client, list_cases_input, format).await?;
+                    }
+                    CaseSubcommand::Watch(watch) => {
+                        watch_case_status(
+                            &client,
+                            watch.case_number,
+                            watch.interval,
+                            watch.until_complete,
+                        )
+                        .await?;
+                    }
+                    CaseSubcommand::OpenUrl(open_url) => {
+                        open_case_url(&client, open_url.case_number, open_url.open)?;
+                    }
+                },
+                EpicorSubcommand::Time(time) => match time.subcommand {
+                    TimeEntrySubcommand::Add(entry) => {
+                        let time_entry = TimeEntry::new(
+                            entry.employee_id,
+                            entry.labor_type,
+                            entry.project_id,
+                            entry.wbs_phase_id,
+                            entry.operation,
+                            entry.plant.or(default_plant()),
+                            entry.expense_code,
+                            entry.hours,
+                        );
+                        send_time_entry(&client, time_entry).await?;
+                    }
+                    TimeEntrySubcommand::List(list) => {
+                        let date = list.date.unwrap_or(chrono::Local::now().date_naive());
+                        list_time_entries(&client, list.employee_id, date, args.json).await?;
+                    }
+                },
+                EpicorSubcommand::Ping => {
+                    ping(&client).await?;
                 }
-                CaseSubcommand::GetStatus(case) => {
-                    get_case_status(case.case_number).await?;
-                }
-                CaseSubcommand::GetCommentSummary(case) => {
-                    println!("Get Comment Summary");
-                }
-                CaseSubcommand::AddComment(case) => {
-                    add_case_comment(case.case_number, case.comment.as_str()).await?;
-                }
-                CaseSubcommand::UpdateQuote(case) => {
-                    update_case_quote(case.case_number, case.new_quantity).await?;
-                }
-                CaseSubcommand::GetLastComment(case) => {
-                    get_last_case_comment(case.case_number).await?;
-                }
-            },
+            }
+        }
+        EntityType::Doctor => {
+            doctor().await?;
+        }
+        EntityType::Config(config) => match config.subcommand {
+            ConfigSubcommand::Show(show) => {
+                let format = if args.json {
+                    ConfigFormat::Json
+                } else {
+                    show.format
+                };
+                show_config(format)?;
+            }
+            ConfigSubcommand::Set(set) => {
+                let value = read_secret_value(&set.value)?;
+                set_config_value(&set.key, &value)?;
+            }
         },
-        EntityType::Setup(setup_info) => {
+        EntityType::Setup(mut setup_info) => {
+            // Decide on the wizard before secrets are filled in from files or the environment.
+            // Without a terminal to prompt on, bare `omni setup` lists the missing options instead.
+            let run_wizard = setup_info.interactive
+                || (setup_info.is_empty() && io::stdin().is_terminal());
+
+            resolve_secret_sources(&mut setup_info)?;
+
+            // There's no need to ping Epicor again once the wizard has tested the connection
+            let connection_tested = if run_wizard {
+                run_setup_wizard(&mut setup_info).await?
+            } else {
+                false
+            };
+
             setup(
                 setup_info.bw_client_id.as_deref(),
                 setup_info.bw_client_secret.as_deref(),
@@ -79,9 +491,16 @@ async fn main() -> Result<()> {
                 setup_info.epicor_username.as_deref(),
                 setup_info.epicor_password.as_deref(),
                 setup_info.openai_api_key.as_deref(),
+                setup_info.bw_server.as_deref(),
+                setup_info.bw_sha256.as_deref(),
+                setup_info.system_install,
+                setup_info.force_download,
+                !setup_info.no_verify && !connection_tested,
+                setup_info.proxy.as_deref(),
+                args.profile.as_deref(),
             )
             .await
-            .expect("Setup Failed.");
+            .map_err(|e| anyhow!("Setup failed: {}", e))?;
         }
     }
     Ok(())
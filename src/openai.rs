@@ -0,0 +1,95 @@
+use crate::config::secret;
+use anyhow::{anyhow, Result};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::time::Duration;
+
+const CHAT_COMPLETIONS_URL: &str = "https://api.openai.com/v1/chat/completions";
+
+/// Model used when OPENAI_MODEL isn't set
+const DEFAULT_MODEL: &str = "gpt-3.5-turbo";
+
+/// Summaries are a one-shot convenience, so don't keep the user waiting long
+const REQUEST_TIMEOUT_SECS: u64 = 60;
+
+const SUMMARY_PROMPT: &str = "You summarize Epicor case comment threads. \
+Reply with a concise summary of 3 to 5 bullet points, each starting with \"- \". \
+Focus on decisions, open questions and who is responsible for the next step.";
+
+#[derive(Serialize, Debug)]
+struct ChatCompletionRequest<'a> {
+    model: String,
+    messages: Vec<ChatMessage<'a>>,
+    temperature: f32,
+}
+
+#[derive(Serialize, Debug)]
+struct ChatMessage<'a> {
+    role: &'a str,
+    content: &'a str,
+}
+
+#[derive(Deserialize, Debug)]
+struct ChatCompletionResponse {
+    choices: Vec<ChatChoice>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ChatChoice {
+    message: ChatResponseMessage,
+}
+
+#[derive(Deserialize, Debug)]
+struct ChatResponseMessage {
+    content: String,
+}
+
+/// Asks the OpenAI chat API for a short bullet summary of a case comment thread
+pub async fn summarize_comments(thread: &str) -> Result<String> {
+    let api_key = secret("OPENAI_API_KEY")
+        .filter(|key| !key.trim().is_empty())
+        .ok_or(anyhow!("OPENAI_API_KEY is not set"))?;
+
+    let model = env::var("OPENAI_MODEL").unwrap_or(DEFAULT_MODEL.to_string());
+
+    let request = ChatCompletionRequest {
+        model,
+        messages: vec![
+            ChatMessage {
+                role: "system",
+                content: SUMMARY_PROMPT,
+            },
+            ChatMessage {
+                role: "user",
+                content: thread,
+            },
+        ],
+        temperature: 0.2,
+    };
+
+    let client = Client::builder()
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .build()?;
+
+    let resp = client
+        .post(CHAT_COMPLETIONS_URL)
+        .bearer_auth(api_key)
+        .json(&request)
+        .send()
+        .await?;
+
+    if !resp.status().is_success() {
+        return Err(anyhow!("OpenAI returned {}", resp.status()));
+    }
+
+    let completion: ChatCompletionResponse = resp.json().await?;
+
+    completion
+        .choices
+        .into_iter()
+        .next()
+        .map(|choice| choice.message.content.trim().to_string())
+        .filter(|summary| !summary.is_empty())
+        .ok_or(anyhow!("OpenAI returned an empty summary"))
+}
@@ -0,0 +1,174 @@
+use anyhow::{anyhow, Result};
+use colored::Colorize;
+use indicatif::ProgressBar;
+use serde::Serialize;
+use std::fs::File;
+use std::io::{self, IsTerminal, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+/// Set by the global --output flag, receives the command's data instead of stdout
+static OUTPUT_FILE: OnceLock<Mutex<File>> = OnceLock::new();
+
+/// Set by the global --quiet flag, hides status messages so only the command's data is printed
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Whether a spinner is on screen. Only one is shown at a time, since concurrent requests
+/// (e.g. get-status with several cases) would otherwise draw over each other.
+static SPINNER_ACTIVE: AtomicBool = AtomicBool::new(false);
+
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// Set by the global --compact flag, or when the output isn't a terminal
+static COMPACT_JSON: AtomicBool = AtomicBool::new(false);
+
+/// Chooses single-line JSON (e.g. for log pipelines) over indented JSON for all --json output
+pub fn set_compact_json(compact: bool) {
+    COMPACT_JSON.store(compact, Ordering::Relaxed);
+}
+
+/// Serializes --json output, indented for people or on one line when compact
+pub fn to_json<T: Serialize + ?Sized>(value: &T) -> Result<String> {
+    let json = if COMPACT_JSON.load(Ordering::Relaxed) {
+        serde_json::to_string(value)?
+    } else {
+        serde_json::to_string_pretty(value)?
+    };
+
+    Ok(json)
+}
+
+/// Shows a spinner on stderr while a slow operation (e.g. an Epicor request) runs, and clears it
+/// when dropped. Nothing is shown with --quiet or when stdout isn't a terminal, so scripted output
+/// stays clean.
+pub struct Spinner(Option<ProgressBar>);
+
+impl Spinner {
+    pub fn start(message: &str) -> Self {
+        let already_active = SPINNER_ACTIVE.swap(true, Ordering::Relaxed);
+
+        if already_active {
+            return Self(None);
+        }
+
+        if is_quiet() || !io::stdout().is_terminal() {
+            SPINNER_ACTIVE.store(false, Ordering::Relaxed);
+            return Self(None);
+        }
+
+        let spinner = ProgressBar::new_spinner();
+        spinner.set_message(message.to_string());
+        spinner.enable_steady_tick(Duration::from_millis(100));
+
+        Self(Some(spinner))
+    }
+}
+
+impl Drop for Spinner {
+    fn drop(&mut self) {
+        if let Some(spinner) = &self.0 {
+            spinner.finish_and_clear();
+            SPINNER_ACTIVE.store(false, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Sends all further command data to the given file, creating or truncating it
+pub fn set_output_file(path: &Path) -> Result<()> {
+    let file = File::create(path)
+        .map_err(|e| anyhow!("Failed to create output file {}: {}", path.display(), e))?;
+
+    OUTPUT_FILE
+        .set(Mutex::new(file))
+        .map_err(|_| anyhow!("Output file already set"))
+}
+
+/// Writes a line of command data to the --output file, or to stdout when there isn't one
+pub fn write_data_line(line: &str) {
+    let Some(file) = OUTPUT_FILE.get() else {
+        println!("{}", line);
+        return;
+    };
+
+    let mut file = file.lock().unwrap_or_else(|e| e.into_inner());
+
+    // Like println!, a failed write can't be recovered from
+    if let Err(e) = writeln!(file, "{}", line) {
+        panic!("failed writing to output file: {}", e);
+    }
+}
+
+/// println! for command data (e.g. a case's status or a list of cases), as opposed to status
+/// messages, so that it can be sent to the --output file
+macro_rules! data_println {
+    () => {
+        $crate::output::write_data_line("")
+    };
+    ($($arg:tt)*) => {
+        $crate::output::write_data_line(&format!($($arg)*))
+    };
+}
+
+/// Prints a status message (e.g. "Login successful") to stderr, keeping stdout for the command's
+/// data so it can be piped. --quiet hides them.
+macro_rules! status_println {
+    ($($arg:tt)*) => {
+        if !$crate::output::is_quiet() {
+            eprintln!($($arg)*)
+        }
+    };
+}
+
+pub(crate) use data_println;
+pub(crate) use status_println;
+
+/// Prints rows as a table under an underlined header row
+pub fn print_table<const N: usize>(headers: [&str; N], rows: &[[String; N]]) {
+    // Size every column to its widest value so the table lines up
+    let mut widths = headers.map(|header| header.chars().count());
+    for row in rows {
+        for (width, value) in widths.iter_mut().zip(row.iter()) {
+            *width = (*width).max(value.chars().count());
+        }
+    }
+
+    let header_line: Vec<String> = headers
+        .iter()
+        .zip(widths.iter())
+        .map(|(header, width)| format!("{:<width$}", header, width = width))
+        .collect();
+    data_println!("{}", header_line.join("  ").red().bold().underline());
+
+    for row in rows {
+        let line: Vec<String> = row
+            .iter()
+            .zip(widths.iter())
+            .map(|(value, width)| format!("{:<width$}", value, width = width))
+            .collect();
+        data_println!("{}", line.join("  "));
+    }
+}
+
+/// Prints `label: value` lines with the values lined up after the longest label, so the output
+/// reads the same with colors turned off
+pub fn print_fields(fields: &[(&str, String)]) {
+    let width = fields
+        .iter()
+        .map(|(label, _)| label.chars().count())
+        .max()
+        .unwrap_or(0);
+
+    for (label, value) in fields {
+        let padding = " ".repeat(width - label.chars().count());
+
+        data_println!("{}:{} {}", label.red().bold(), padding, value);
+    }
+}
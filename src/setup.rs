@@ -1,22 +1,202 @@
+use crate::args::SetupCommand;
+use crate::bitwarden::config_server;
+use crate::config::{env_file_path, env_line, save_profile, write_private_file, Profile};
+use crate::epicor::{
+    api_version, client_builder, company, connection_error_message, normalize_base_url,
+};
+use crate::output::Spinner;
+use anyhow::anyhow;
 use base64::engine::general_purpose;
 use base64::Engine;
+use colored::Colorize;
+use dialoguer::{Confirm, Input, Password};
+use reqwest::header::AUTHORIZATION;
+use reqwest::Url;
+use sha2::{Digest, Sha256};
 use std::env;
 use std::error::Error;
-use std::ffi::OsString;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::{self, Read, Write};
 use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Arc, Mutex};
 use zip::ZipArchive;
 
-fn download_and_extract(url: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+/// Bitwarden publishes a SHA-256 checksum file next to each CLI release zip,
+/// e.g. bw-linux-sha256-2023.5.0.txt for bw-linux-2023.5.0.zip
+fn checksum_url(download_url: &Url) -> Option<String> {
+    let file_name = download_url.path_segments()?.next_back()?;
+    let (prefix, version) = file_name.strip_suffix(".zip")?.rsplit_once('-')?;
+
+    Some(download_url.as_str().replace(file_name, &format!("{}-sha256-{}.txt", prefix, version)))
+}
+
+/// The download link redirects to the GitHub release, which in turn redirects to a storage URL
+/// without the file name, so the release is the last URL in the chain that names the zip
+fn release_url(redirects: &[Url]) -> Option<&Url> {
+    redirects.iter().rev().find(|url| {
+        url.path_segments()
+            .and_then(|mut segments| segments.next_back())
+            .is_some_and(|file_name| file_name.ends_with(".zip"))
+    })
+}
+
+fn fetch_published_sha256(download_url: &Url) -> Result<String, Box<dyn Error + Send + Sync>> {
+    let checksum_url = checksum_url(download_url).ok_or(format!(
+        "Could not find a published checksum for {}. Pass --bw-sha256 to verify it against a known hash.",
+        download_url
+    ))?;
+
+    let response = reqwest::blocking::get(&checksum_url)
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| {
+            format!(
+                "Failed to download Bitwarden CLI checksum from {}: {}",
+                checksum_url,
+                connection_error_message(&e)
+            )
+        })?;
+
+    // The file holds the hex digest, sometimes followed by the file name
+    let checksum = response.text()?;
+    let sha256 = checksum
+        .split_whitespace()
+        .next()
+        .ok_or(format!("Bitwarden CLI checksum file {} is empty", checksum_url))?;
+
+    Ok(sha256.to_lowercase())
+}
+
+/// Returns the version of the Bitwarden CLI if a working copy is on PATH, along with its location
+pub(crate) fn installed_bw() -> Option<(String, Option<PathBuf>)> {
+    let output = Command::new("bw").arg("--version").output().ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    let version = String::from_utf8_lossy(&output.stdout).trim().to_string();
+
+    let bw_name = if cfg!(windows) { "bw.exe" } else { "bw" };
+    let path = env::var_os("PATH").and_then(|path| {
+        env::split_paths(&path)
+            .map(|dir| dir.join(bw_name))
+            .find(|path| path.is_file())
+    });
+
+    Some((version, path))
+}
+
+/// Returns where the Bitwarden CLI is installed. By default this is a user-writable directory,
+/// the system directories need admin rights and are only used when asked for.
+fn bw_install_dir(system_install: bool) -> Result<PathBuf, Box<dyn Error + Send + Sync>> {
+    let os = env::consts::OS;
+
+    let install_dir = match (os, system_install) {
+        ("windows", true) => PathBuf::from("C:\\Windows\\System32"),
+        ("macos" | "linux", true) => PathBuf::from("/usr/local/bin"),
+        ("windows", false) => dirs::data_local_dir()
+            .ok_or("Failed to find the local data directory")?
+            .join("omni")
+            .join("bin"),
+        ("macos" | "linux", false) => dirs::home_dir()
+            .ok_or("Failed to find the home directory")?
+            .join(".local")
+            .join("bin"),
+        _ => {
+            return Err(format!("Unsupported operating system: {}", os).into());
+        }
+    };
+
+    Ok(install_dir)
+}
+
+/// Explains a failed write to the install directory instead of surfacing the raw IO error
+fn install_error(path: &Path, e: io::Error) -> Box<dyn Error + Send + Sync> {
+    if e.kind() == io::ErrorKind::PermissionDenied {
+        return format!(
+            "Permission denied writing the Bitwarden CLI to {}. Run setup with admin rights, or without --system-install to install it for the current user.",
+            path.display()
+        )
+        .into();
+    }
+
+    format!("Failed to write the Bitwarden CLI to {}: {}", path.display(), e).into()
+}
+
+fn download_and_extract(
+    url: &str,
+    expected_sha256: Option<&str>,
+    install_dir: &Path,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    // Remember every URL the download redirects through, to find the release it came from
+    let redirects = Arc::new(Mutex::new(Vec::new()));
+    let visited = Arc::clone(&redirects);
+
+    let client = reqwest::blocking::Client::builder()
+        .redirect(reqwest::redirect::Policy::custom(move |attempt| {
+            visited.lock().unwrap().push(attempt.url().clone());
+
+            if attempt.previous().len() > 10 {
+                attempt.error("too many redirects")
+            } else {
+                attempt.follow()
+            }
+        }))
+        .build()?;
+
     // Perform the HTTP request
-    let response = reqwest::blocking::get(url)?;
+    // Proxies set with HTTPS_PROXY / HTTP_PROXY (or --proxy) are picked up by reqwest
+    let response = client.get(url).send().map_err(|e| {
+        format!(
+            "Failed to download Bitwarden CLI from {}: {}",
+            url,
+            connection_error_message(&e)
+        )
+    })?;
+
+    // Anything other than a success is not the ZIP we're after, so don't try to unpack it
+    if !response.status().is_success() {
+        return Err(format!(
+            "Failed to download Bitwarden CLI from {}: {}",
+            url,
+            response.status()
+        )
+        .into());
+    }
+
+    // The checksum is published for the versioned release the download link redirects to
+    let download_url = release_url(&redirects.lock().unwrap())
+        .unwrap_or(response.url())
+        .clone();
+
+    // Read the full body before touching the disk
+    let bytes = response
+        .bytes()
+        .map_err(|e| format!("Failed to download Bitwarden CLI from {}: {}", url, e))?;
+
+    // Verify the archive against the pinned hash, or the one published with the release,
+    // so that a corrupted or tampered download is never installed
+    let expected_sha256 = match expected_sha256 {
+        Some(sha256) => sha256.trim().to_lowercase(),
+        None => fetch_published_sha256(&download_url)?,
+    };
+
+    let actual_sha256 = format!("{:x}", Sha256::digest(&bytes));
+
+    if actual_sha256 != expected_sha256 {
+        return Err(format!(
+            "Bitwarden CLI checksum mismatch for {}: expected {}, got {}. The download was not installed.",
+            download_url, expected_sha256, actual_sha256
+        )
+        .into());
+    }
 
     // Create a temporary file to store the downloaded ZIP
     let mut temp_file = tempfile::NamedTempFile::new()?;
 
     // Write the response to the temporary file
-    io::copy(&mut response.bytes().unwrap().as_ref(), &mut temp_file)?;
+    io::copy(&mut bytes.as_ref(), &mut temp_file)?;
 
     // Open the downloaded ZIP file
     let zip_file = File::open(temp_file.path())?;
@@ -27,23 +207,14 @@ fn download_and_extract(url: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
     let mut buffer = Vec::new();
     bw_file.read_to_end(&mut buffer)?;
 
-    // Determine the appropriate path to add "bw" to the system path
-    let os = env::consts::OS;
-    let system_path = match os {
-        "windows" => "C:\\Windows\\System32", // Modify this as needed
-        "macos" => "/usr/local/bin",          // Modify this as needed
-        "linux" => "/usr/local/bin",          // Modify this as needed
-        _ => {
-            return Err(format!("Unsupported operating system: {}", os).into());
-        }
-    };
+    // Create the install directory and write the "bw" file to it
+    fs::create_dir_all(install_dir).map_err(|e| install_error(install_dir, e))?;
 
-    // Create the path and write the "bw" file to it
-    let path = Path::new(system_path).join("bw");
-    let mut file = File::create(&path)?;
-    file.write_all(&buffer)?;
+    let path = install_dir.join("bw");
+    let mut file = File::create(&path).map_err(|e| install_error(&path, e))?;
+    file.write_all(&buffer).map_err(|e| install_error(&path, e))?;
 
-    // Make the file executable
+    // Make the file executable (this also covers MacOS, which is a unix target)
     #[cfg(unix)]
     {
         use std::os::unix::fs::PermissionsExt;
@@ -52,27 +223,18 @@ fn download_and_extract(url: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
         file.set_permissions(permissions)?;
     }
 
-    // Make the file executable for Windows
+    // Windows has no executable bit, the file only needs to be writable
     #[cfg(windows)]
     {
-        use std::os::windows::fs::PermissionsExt;
         let mut permissions = file.metadata()?.permissions();
         permissions.set_readonly(false);
-        permissions.set_mode(0o755);
         file.set_permissions(permissions)?;
     }
 
-    // Make the file executable for MacOS
-    #[cfg(macos)]
-    {
-        use std::os::macos::fs::PermissionsExt;
-        let mut permissions = file.metadata()?.permissions();
-        permissions.set_readonly(false);
-        permissions.set_mode(0o755);
-        file.set_permissions(permissions)?;
-    }
+    println!("Installed the Bitwarden CLI to {}", path.display());
 
     // Add the path to the system path environment variable
+    let os = env::consts::OS;
     let path_var = match os {
         "windows" => "Path",
         _ => "PATH",
@@ -83,8 +245,17 @@ fn download_and_extract(url: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
     // Split the current_path into components
     let mut paths: Vec<_> = env::split_paths(&current_path).collect();
 
-    // Add the new system_path to the paths list
-    paths.push(PathBuf::from(system_path));
+    // The PATH change below only lasts for this process, so tell the user to make it permanent
+    if !paths.iter().any(|path| path == install_dir) {
+        println!(
+            "{} {} is not on your PATH. Add it to your shell profile so that `bw` can be found.",
+            "Note:".yellow().bold(),
+            install_dir.display()
+        );
+    }
+
+    // Add the install directory to the paths list
+    paths.push(install_dir.to_path_buf());
 
     // Join all paths together
     let new_path = env::join_paths(paths)?;
@@ -95,94 +266,228 @@ fn download_and_extract(url: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
     Ok(())
 }
 
-fn create_env_file(
-    client_id: Option<&str>,
-    client_secret: Option<&str>,
-    master_password: Option<&str>,
-    epicor_base_url: Option<&str>,
-    epicor_api_key: Option<&str>,
-    epicor_basic_auth: Option<&str>,
-    openai_api_key: Option<&str>,
-) -> Result<(), Box<dyn Error + Send + Sync>> {
-    let os = env::consts::OS;
+fn create_env_file(settings: &Profile) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let env_file_path = env_file_path()?;
 
-    let env_file_path = match os {
-        "windows" | "macos" | "linux" => {
-            let mut path = env::current_dir()?;
-            path.push("./.env");
-            path
-        }
-        _ => {
-            return Err(format!("Unsupported operating system: {}", os).into());
-        }
+    // Make sure the config directory exists before writing to it
+    if let Some(parent) = env_file_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut contents = String::new();
+
+    for (name, value) in settings.env_vars() {
+        let Some(value) = value else {
+            continue;
+        };
+
+        contents.push_str(&format!("{}\n", env_line(name, value)));
+    }
+
+    // Ensure that only the current user can read the file
+    write_private_file(&env_file_path, &contents)?;
+
+    Ok(())
+}
+
+pub(crate) fn generate_basic_auth(username: &str, password: &str) -> String {
+    let auth_str = format!("{}:{}", username, password);
+    let encoded_auth_str = general_purpose::STANDARD.encode(auth_str.as_bytes());
+    format!("Basic {}", encoded_auth_str)
+}
+
+fn prompt_text(prompt: &str) -> anyhow::Result<String> {
+    Ok(Input::<String>::new().with_prompt(prompt).interact_text()?)
+}
+
+fn prompt_secret(prompt: &str) -> anyhow::Result<String> {
+    Ok(Password::new().with_prompt(prompt).interact()?)
+}
+
+fn prompt_url(prompt: &str) -> anyhow::Result<String> {
+    Ok(Input::<String>::new()
+        .with_prompt(prompt)
+        .validate_with(|input: &String| -> Result<(), &str> {
+            match Url::parse(input) {
+                Ok(url) if url.scheme() == "http" || url.scheme() == "https" => Ok(()),
+                _ => Err("Please enter a full http(s) URL, e.g. https://epicor.example.com/ERP"),
+            }
+        })
+        .interact_text()?)
+}
+
+/// Makes a lightweight authenticated request to check that Epicor accepts the given credentials
+async fn test_epicor_connection(
+    epicor_base_url: &str,
+    epicor_api_key: &str,
+    epicor_basic_auth: &str,
+) -> anyhow::Result<()> {
+    let url = format!(
+        "{}/api/{}/odata/{}/",
+        normalize_base_url(epicor_base_url)?,
+        api_version()?,
+        company()?
+    );
+
+    let resp = client_builder()?
+        .build()?
+        .get(&url)
+        .header("X-API-Key", epicor_api_key)
+        .header(AUTHORIZATION, epicor_basic_auth)
+        .send()
+        .await?;
+
+    match resp.status().as_u16() {
+        200..=299 => Ok(()),
+        401 => Err(anyhow!("Epicor rejected the API key or username/password")),
+        _ => Err(anyhow!("Epicor returned {}", resp.status())),
+    }
+}
+
+/// Reads a secret flag's value: `@path` reads it from a file and `-` from stdin,
+/// anything else is used as is
+pub(crate) fn read_secret_value(value: &str) -> anyhow::Result<String> {
+    let secret = if value == "-" {
+        let mut secret = String::new();
+        io::stdin().read_line(&mut secret)?;
+        secret
+    } else if let Some(path) = value.strip_prefix('@') {
+        fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read secret from {}: {}", path, e))?
+    } else {
+        return Ok(value.to_string());
     };
 
-    let mut env_file = File::create(&env_file_path)?;
+    // Files and piped input usually end with a newline that isn't part of the secret
+    Ok(secret.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Resolves the secret setup flags given as `@file` or `-`, and falls back to the
+/// BW_MASTER_PASSWORD and EPICOR_PASSWORD environment variables, so that secrets
+/// don't have to be passed on the command line
+pub(crate) fn resolve_secret_sources(setup_info: &mut SetupCommand) -> anyhow::Result<()> {
+    let secrets = [
+        ("--bw-client-secret", &mut setup_info.bw_client_secret),
+        ("--bw-master-password", &mut setup_info.bw_master_password),
+        ("--epicor-api-key", &mut setup_info.epicor_api_key),
+        ("--epicor-password", &mut setup_info.epicor_password),
+        ("--openai-api-key", &mut setup_info.openai_api_key),
+    ];
+
+    // stdin can only be read once
+    let stdin_flags: Vec<&str> = secrets
+        .iter()
+        .filter(|(_, value)| value.as_deref() == Some("-"))
+        .map(|(flag, _)| *flag)
+        .collect();
+
+    if stdin_flags.len() > 1 {
+        return Err(anyhow!(
+            "Only one secret can be read from stdin, but {} were given as -",
+            stdin_flags.join(", ")
+        ));
+    }
 
-    if let Some(client_id) = client_id {
-        env_file.write_all(format!("BW_CLIENTID={}\n", client_id).as_bytes())?;
+    for (_, value) in secrets {
+        if let Some(source) = value.as_deref() {
+            *value = Some(read_secret_value(source)?);
+        }
     }
 
-    if let Some(client_secret) = client_secret {
-        env_file.write_all(format!("BW_CLIENTSECRET={}\n", client_secret).as_bytes())?;
+    if setup_info.bw_master_password.is_none() {
+        setup_info.bw_master_password = env::var("BW_MASTER_PASSWORD").ok();
     }
 
-    if let Some(master_password) = master_password {
-        env_file.write_all(format!("MASTER_PASSWORD={}\n", master_password).as_bytes())?;
+    if setup_info.epicor_password.is_none() {
+        setup_info.epicor_password = env::var("EPICOR_PASSWORD").ok();
     }
 
-    if let Some(epicor_base_url) = epicor_base_url {
-        env_file.write_all(format!("EPICOR_BASE_URL={}\n", epicor_base_url).as_bytes())?;
+    Ok(())
+}
+
+/// Prompts for every setup value that wasn't passed as a flag. Returns whether the Epicor
+/// connection was tested along the way.
+pub(crate) async fn run_setup_wizard(setup_info: &mut SetupCommand) -> anyhow::Result<bool> {
+    println!("{}", "Omni Setup".bright_green().bold().underline());
+
+    if setup_info.bw_client_id.is_none() {
+        setup_info.bw_client_id = Some(prompt_text("BitWarden Client ID")?);
     }
 
-    if let Some(epicor_api_key) = epicor_api_key {
-        env_file.write_all(format!("EPICOR_API_KEY={}\n", epicor_api_key).as_bytes())?;
+    if setup_info.bw_client_secret.is_none() {
+        setup_info.bw_client_secret = Some(prompt_secret("BitWarden Client Secret")?);
     }
 
-    if let Some(epicor_basic_auth) = epicor_basic_auth {
-        env_file.write_all(format!("EPICOR_BASIC_AUTH='{}'\n", epicor_basic_auth).as_bytes())?;
+    if setup_info.bw_master_password.is_none() {
+        setup_info.bw_master_password = Some(prompt_secret("BitWarden Master Password")?);
     }
 
-    if let Some(openai_api_key) = openai_api_key {
-        env_file.write_all(format!("OPENAI_API_KEY={}\n", openai_api_key).as_bytes())?;
+    if setup_info.epicor_base_url.is_none() {
+        setup_info.epicor_base_url = Some(prompt_url("Epicor Base URL")?);
     }
 
-    // Ensure that all users have read/write permissions to the file
-    #[cfg(unix)]
-    {
-        use std::os::unix::fs::PermissionsExt;
-        let mut permissions = env_file.metadata()?.permissions();
-        permissions.set_mode(0o666);
-        env_file.set_permissions(permissions)?;
+    if setup_info.epicor_api_key.is_none() {
+        setup_info.epicor_api_key = Some(prompt_secret("Epicor API Key")?);
     }
 
-    // Windows
-    #[cfg(windows)]
-    {
-        use std::os::windows::fs::PermissionsExt;
-        let mut permissions = env_file.metadata()?.permissions();
-        permissions.set_mode(0o666);
-        env_file.set_permissions(permissions)?;
+    if setup_info.epicor_username.is_none() {
+        setup_info.epicor_username = Some(prompt_text("Epicor Username")?);
     }
 
-    // MacOS
-    #[cfg(macos)]
-    {
-        use std::os::macos::fs::PermissionsExt;
-        let mut permissions = env_file.metadata()?.permissions();
-        permissions.set_mode(0o666);
-        env_file.set_permissions(permissions)?;
+    if setup_info.epicor_password.is_none() {
+        setup_info.epicor_password = Some(prompt_secret("Epicor Password")?);
     }
 
-    Ok(())
-}
+    // The OpenAI key is optional, so an empty answer skips it
+    if setup_info.openai_api_key.is_none() {
+        let openai_api_key = Input::<String>::new()
+            .with_prompt("OpenAI API Key (optional)")
+            .allow_empty(true)
+            .interact_text()?;
 
-fn generate_basic_auth(username: &str, password: &str) -> String {
-    let auth_str = format!("{}:{}", username, password);
-    let encoded_auth_str = general_purpose::STANDARD.encode(auth_str.as_bytes());
-    return format!("Basic {}", encoded_auth_str);
+        if !openai_api_key.trim().is_empty() {
+            setup_info.openai_api_key = Some(openai_api_key);
+        }
+    }
+
+    let test_connection = Confirm::new()
+        .with_prompt("Test the Epicor connection before saving?")
+        .default(true)
+        .interact()?;
+
+    if test_connection {
+        if let (Some(epicor_base_url), Some(epicor_api_key), Some(epicor_username), Some(epicor_password)) = (
+            setup_info.epicor_base_url.as_deref(),
+            setup_info.epicor_api_key.as_deref(),
+            setup_info.epicor_username.as_deref(),
+            setup_info.epicor_password.as_deref(),
+        ) {
+            let epicor_basic_auth = generate_basic_auth(epicor_username, epicor_password);
+
+            match test_epicor_connection(epicor_base_url, epicor_api_key, &epicor_basic_auth).await {
+                Ok(_) => println!("{}", "Epicor connection successful".bright_green().bold()),
+                Err(e) => {
+                    println!("{} {}", "Epicor connection failed:".red().bold(), e);
+
+                    let save_anyway = Confirm::new()
+                        .with_prompt("Save these settings anyway?")
+                        .default(false)
+                        .interact()?;
+
+                    if !save_anyway {
+                        return Err(anyhow!("Setup cancelled"));
+                    }
+                }
+            }
+
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
 }
 
+#[allow(clippy::too_many_arguments)]
 pub(crate) async fn setup(
     client_id: Option<&str>,
     client_secret: Option<&str>,
@@ -192,66 +497,210 @@ pub(crate) async fn setup(
     epicor_username: Option<&str>,
     epicor_password: Option<&str>,
     openai_api_key: Option<&str>,
+    bw_server: Option<&str>,
+    bw_sha256: Option<&str>,
+    system_install: bool,
+    force_download: bool,
+    verify: bool,
+    proxy: Option<&str>,
+    profile: Option<&str>,
 ) -> Result<(), Box<dyn Error>> {
-    let os = env::consts::OS;
+    // Check every required option up front so nothing is downloaded or written
+    // when the command can't complete, and so all the omissions are reported at once.
+    let required_options = [
+        ("--bw-client-id", client_id),
+        ("--bw-client-secret", client_secret),
+        ("--bw-master-password", master_password),
+        ("--epicor-base-url", epicor_base_url),
+        ("--epicor-api-key", epicor_api_key),
+        ("--epicor-username", epicor_username),
+        ("--epicor-password", epicor_password),
+    ];
+
+    let missing_options: Vec<&str> = required_options
+        .iter()
+        .filter(|(_, value)| value.is_none())
+        .map(|(flag, _)| *flag)
+        .collect();
+
+    let (
+        Some(client_id),
+        Some(client_secret),
+        Some(master_password),
+        Some(epicor_base_url),
+        Some(epicor_api_key),
+        Some(epicor_username),
+        Some(epicor_password),
+    ) = (
+        client_id,
+        client_secret,
+        master_password,
+        epicor_base_url,
+        epicor_api_key,
+        epicor_username,
+        epicor_password,
+    )
+    else {
+        return Err(anyhow!(
+            "Missing required setup options: {}",
+            missing_options.join(", ")
+        )
+        .into());
+    };
 
-    match os {
-        "windows" => {
-            tokio::task::spawn_blocking(|| -> Result<(), Box<dyn Error + Send + Sync>> {
-                download_and_extract(
-                    "https://vault.bitwarden.com/download/?app=cli&platform=windows",
-                )
-            })
-            .await?
-            .expect("TODO: panic message");
-        }
-        "macos" => {
-            tokio::task::spawn_blocking(|| -> Result<(), Box<dyn Error + Send + Sync>> {
-                download_and_extract("https://vault.bitwarden.com/download/?app=cli&platform=macos")
-            })
-            .await?
-            .expect("TODO: panic message");
-        }
-        "linux" => {
-            tokio::task::spawn_blocking(|| -> Result<(), Box<dyn Error + Send + Sync>> {
-                download_and_extract("https://vault.bitwarden.com/download/?app=cli&platform=linux")
-            })
-            .await?
-            .expect("TODO: panic message");
-        }
-        _ => {
-            println!("Unsupported operating system: {}", os);
-            return Ok(());
+    // Catch a mistyped URL before anything is downloaded, and save it without a pasted /api/v2
+    let epicor_base_url = normalize_base_url(epicor_base_url)?;
+
+    if let Some(bw_server) = bw_server {
+        match Url::parse(bw_server) {
+            Ok(url) if url.scheme() == "http" || url.scheme() == "https" => {}
+            _ => return Err(anyhow!("Invalid Bitwarden server URL {}", bw_server).into()),
         }
     }
 
-    let epicor_basic_auth = match (epicor_username, epicor_password) {
-        (Some(username), Some(password)) => generate_basic_auth(username, password),
-        _ => String::new(),
+    // Route this run's downloads, the connection check and bw itself through the proxy,
+    // and save it so later commands use it too
+    if let Some(proxy) = proxy {
+        reqwest::Proxy::all(proxy).map_err(|e| anyhow!("Invalid proxy URL {}: {}", proxy, e))?;
+        env::set_var("HTTPS_PROXY", proxy);
+        env::set_var("HTTP_PROXY", proxy);
+    }
+
+    // Re-running setup to refresh credentials shouldn't download the Bitwarden CLI again
+    let existing_bw = if force_download { None } else { installed_bw() };
+
+    if let Some((version, path)) = existing_bw {
+        println!(
+            "Found Bitwarden CLI {} at {}, skipping download (pass --force-download to reinstall)",
+            version,
+            path.map(|path| path.display().to_string()).unwrap_or("PATH".to_string())
+        );
+    } else {
+        let os = env::consts::OS;
+
+        let download_url = match os {
+            "windows" => "https://vault.bitwarden.com/download/?app=cli&platform=windows",
+            "macos" => "https://vault.bitwarden.com/download/?app=cli&platform=macos",
+            "linux" => "https://vault.bitwarden.com/download/?app=cli&platform=linux",
+            _ => {
+                println!("Unsupported operating system: {}", os);
+                return Ok(());
+            }
+        };
+
+        // A pinned hash allows verifying the download without fetching the published checksum
+        let bw_sha256 = bw_sha256
+            .map(|sha256| sha256.to_string())
+            .or(env::var("BW_SHA256").ok());
+
+        let install_dir = bw_install_dir(system_install).map_err(|e| anyhow!("{}", e))?;
+
+        let spinner = Spinner::start("Downloading the Bitwarden CLI…");
+
+        tokio::task::spawn_blocking(move || {
+            download_and_extract(download_url, bw_sha256.as_deref(), &install_dir)
+        })
+        .await?
+        .map_err(|e| anyhow!("{}", e))?;
+
+        drop(spinner);
+    }
+
+    // bw has to point at a self-hosted server before it can log in there
+    if let Some(bw_server) = bw_server {
+        config_server(bw_server)?;
+    }
+
+    let epicor_basic_auth = generate_basic_auth(epicor_username, epicor_password);
+
+    // Keep a copy of the Epicor settings for verifying the connection once they're saved
+    let verify_settings = (
+        epicor_base_url.to_string(),
+        epicor_api_key.to_string(),
+        epicor_basic_auth.clone(),
+    );
+
+    let mut settings = Profile {
+        bw_server: bw_server.map(|bw_server| bw_server.to_string()),
+        bw_client_id: Some(client_id.to_string()),
+        bw_client_secret: Some(client_secret.to_string()),
+        master_password: Some(master_password.to_string()),
+        epicor_base_url: Some(epicor_base_url.to_string()),
+        epicor_api_key: Some(epicor_api_key.to_string()),
+        epicor_basic_auth: Some(epicor_basic_auth),
+        openai_api_key: openai_api_key.map(|key| key.to_string()),
+        https_proxy: proxy.map(|proxy| proxy.to_string()),
+        epicor_default_assignee: env::var("EPICOR_DEFAULT_ASSIGNEE").ok(),
+        epicor_credentials_item: env::var("EPICOR_CREDENTIALS_ITEM").ok(),
+        epicor_case_url_template: env::var("EPICOR_CASE_URL_TEMPLATE").ok(),
+        epicor_default_plant: env::var("EPICOR_DEFAULT_PLANT").ok(),
+        epicor_company: env::var("EPICOR_COMPANY").ok(),
+        epicor_api_version: env::var("EPICOR_API_VERSION").ok(),
+        epicor_ca_cert: env::var("EPICOR_CA_CERT").ok(),
+        epicor_timeout_secs: env::var("EPICOR_TIMEOUT_SECS").ok(),
+        epicor_max_retries: env::var("EPICOR_MAX_RETRIES").ok(),
+        openai_model: env::var("OPENAI_MODEL").ok(),
     };
 
-    let client_id = client_id.unwrap().to_string();
-    let client_secret = client_secret.unwrap().to_string();
-    let master_password = master_password.unwrap().to_string();
-    let epicor_base_url = epicor_base_url.unwrap().to_string();
-    let epicor_api_key = epicor_api_key.unwrap().to_string();
-    let epicor_basic_auth = epicor_basic_auth;
-    let openai_api_key = openai_api_key.unwrap().to_string();
-
-    tokio::task::spawn_blocking(move || -> Result<(), Box<dyn Error + Send + Sync>> {
-        create_env_file(
-            Some(&client_id),
-            Some(&client_secret),
-            Some(&master_password),
-            Some(&epicor_base_url),
-            Some(&epicor_api_key),
-            Some(&epicor_basic_auth),
-            Some(&openai_api_key),
-        )
-    })
-    .await?
-    .expect("TODO: panic message");
+    // Keep the secrets in the OS keychain rather than in plaintext on disk
+    settings.move_secrets_to_keyring(profile);
+
+    // Write into the named profile when one is given, otherwise into the flat .env file
+    if let Some(profile) = profile {
+        save_profile(profile, settings)?;
+    } else {
+        tokio::task::spawn_blocking(move || create_env_file(&settings))
+            .await?
+            .map_err(|e| anyhow!("{}", e))?;
+    }
 
     println!("Omni setup complete!");
+
+    // Make sure the saved credentials actually work, rather than finding out on the first case command
+    if verify {
+        let (epicor_base_url, epicor_api_key, epicor_basic_auth) = verify_settings;
+
+        match test_epicor_connection(&epicor_base_url, &epicor_api_key, &epicor_basic_auth).await {
+            Ok(_) => println!("{}", "Epicor connection verified".bright_green().bold()),
+            Err(e) => println!(
+                "{} {}\nCheck the Epicor base URL, API key, username and password, then run setup again.",
+                "Epicor connection could not be verified:".red().bold(),
+                e
+            ),
+        }
+    }
+
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksum_url_names_the_release_checksum_file() {
+        let release = Url::parse(
+            "https://github.com/bitwarden/clients/releases/download/cli-v2023.5.0/bw-linux-2023.5.0.zip",
+        )
+        .unwrap();
+
+        assert_eq!(
+            checksum_url(&release).as_deref(),
+            Some("https://github.com/bitwarden/clients/releases/download/cli-v2023.5.0/bw-linux-sha256-2023.5.0.txt")
+        );
+    }
+
+    #[test]
+    fn release_url_skips_the_storage_redirect() {
+        let redirects = [
+            "https://github.com/bitwarden/clients/releases/download/cli-v2023.5.0/bw-linux-2023.5.0.zip",
+            "https://objects.githubusercontent.com/github-production-release-asset-2e65be/53538899/1a2b3c?X-Amz-Algorithm=AWS4-HMAC-SHA256&response-content-disposition=attachment%3B%20filename%3Dbw-linux-2023.5.0.zip",
+        ]
+        .map(|url| Url::parse(url).unwrap());
+
+        let release = release_url(&redirects).unwrap();
+
+        assert_eq!(release, &redirects[0]);
+        assert!(checksum_url(release).is_some());
+    }
+}
@@ -0,0 +1,96 @@
+#![cfg(unix)]
+
+mod common;
+
+use common::omni;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::process::Command;
+use tempfile::TempDir;
+
+/// A stand-in for the Bitwarden CLI that logs each call. The vault is logged in, is unlocked
+/// only when BW_UNLOCKED is set, rejects the master password and has no items.
+const FAKE_BW: &str = r#"#!/bin/sh
+echo "$*" >> "$BW_LOG"
+case "$*" in
+    "login --check") exit 0 ;;
+    "unlock --check") [ -n "$BW_UNLOCKED" ] && exit 0 || exit 1 ;;
+    unlock*) echo "Invalid master password." >&2; exit 1 ;;
+    get*) echo "Not found." >&2; exit 1 ;;
+esac
+"#;
+
+/// Builds an `omni` command that runs the fake bw, with a cached vault session
+fn omni_with_fake_bw(home: &TempDir) -> Command {
+    let bin_dir = home.path().join("bin");
+    fs::create_dir_all(&bin_dir).unwrap();
+
+    let bw = bin_dir.join("bw");
+    fs::write(&bw, FAKE_BW).unwrap();
+    fs::set_permissions(&bw, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let session_file = session_file(home);
+    fs::create_dir_all(session_file.parent().unwrap()).unwrap();
+    fs::write(&session_file, "cached-session").unwrap();
+
+    let mut command = omni(home);
+
+    command
+        .env("PATH", format!("{}:/usr/bin:/bin", bin_dir.display()))
+        .env("BW_LOG", bw_log(home))
+        .env("MASTER_PASSWORD", "wrong-password");
+
+    command
+}
+
+fn session_file(home: &TempDir) -> PathBuf {
+    home.path().join("config").join("omni").join("bw_session")
+}
+
+fn bw_log(home: &TempDir) -> PathBuf {
+    home.path().join("bw.log")
+}
+
+fn bw_calls(home: &TempDir) -> Vec<String> {
+    fs::read_to_string(bw_log(home))
+        .unwrap_or_default()
+        .lines()
+        .map(str::to_string)
+        .collect()
+}
+
+#[test]
+fn a_missing_item_keeps_the_session() {
+    let home = TempDir::new().unwrap();
+
+    let output = omni_with_fake_bw(&home)
+        .env("BW_UNLOCKED", "1")
+        .args(["bitwarden", "get", "-i", "password", "-n", "CAEL10"])
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No vault item found named CAEL10"));
+    assert!(session_file(&home).exists());
+    assert!(!bw_calls(&home).iter().any(|call| call == "lock" || call == "logout"));
+}
+
+#[test]
+fn a_failed_unlock_ends_the_session() {
+    let home = TempDir::new().unwrap();
+
+    let output = omni_with_fake_bw(&home)
+        .args(["bitwarden", "get", "-i", "password", "-n", "CAEL10"])
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Failed to unlock vault"));
+    assert!(!session_file(&home).exists());
+
+    let calls = bw_calls(&home);
+    assert!(calls.iter().any(|call| call == "lock"));
+    assert!(calls.iter().any(|call| call == "logout"));
+    assert!(!calls.iter().any(|call| call.starts_with("get")));
+}
@@ -0,0 +1,76 @@
+// Each test crate compiles this module on its own and uses only part of it
+#![allow(dead_code)]
+
+use serde_json::Value;
+use std::path::PathBuf;
+use std::process::Command;
+use tempfile::TempDir;
+use wiremock::matchers::path;
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+const FUNCTIONS: &str = "/ERP/api/v2/efx/100/Omni";
+
+/// The path of an Omni function on the mock Epicor server, e.g. Omni/GetCaseStatus
+pub fn function(name: &str) -> String {
+    format!("{}/{}", FUNCTIONS, name)
+}
+
+/// Answers requests to a path on the mock Epicor server with a canned JSON response
+pub async fn respond(server: &MockServer, route: &str, status: u16, body: Value) {
+    Mock::given(path(route))
+        .respond_with(ResponseTemplate::new(status).set_body_json(body))
+        .mount(server)
+        .await;
+}
+
+/// The JSON bodies of the requests the mock Epicor server received, in order
+pub async fn request_bodies(server: &MockServer) -> Vec<Value> {
+    server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .map(|request| request.body_json().unwrap_or(Value::Null))
+        .collect()
+}
+
+pub fn case_status_body() -> Value {
+    serde_json::from_str(include_str!("../fixtures/case_status.json")).unwrap()
+}
+
+/// Builds an `omni` command with its config and cache kept in a temporary home, so a
+/// developer's own settings, cached case statuses and vault session are never touched
+pub fn omni(home: &TempDir) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_omni"));
+
+    command
+        .current_dir(home.path())
+        .env_clear()
+        .env("HOME", home.path())
+        .env("XDG_CONFIG_HOME", home.path().join("config"))
+        .env("XDG_CACHE_HOME", home.path().join("cache"))
+        .env("OMNI_CACHE_DIR", cache_dir(home));
+
+    command
+}
+
+/// Builds an `omni` command that talks to the mock Epicor server, with the function library
+/// under /ERP
+pub fn omni_with_epicor(server: &MockServer, home: &TempDir) -> Command {
+    let mut command = omni(home);
+
+    command
+        .env("EPICOR_BASE_URL", format!("{}/ERP", server.uri()))
+        .env("EPICOR_API_KEY", "test-key")
+        .env("EPICOR_BASIC_AUTH", "Basic dGVzdA==")
+        .env("EPICOR_COMPANY", "100")
+        .env("EPICOR_API_VERSION", "v2")
+        .env("EPICOR_MAX_RETRIES", "0");
+
+    command
+}
+
+/// The directory OMNI_CACHE_DIR points at for a temporary home
+pub fn cache_dir(home: &TempDir) -> PathBuf {
+    home.path().join("omni-cache")
+}
@@ -0,0 +1,95 @@
+mod common;
+
+use common::omni;
+use serde_json::Value;
+use std::fs;
+use std::path::PathBuf;
+use tempfile::TempDir;
+
+fn config_dir(home: &TempDir) -> PathBuf {
+    let config_dir = home.path().join("config").join("omni");
+    fs::create_dir_all(&config_dir).unwrap();
+
+    config_dir
+}
+
+#[test]
+fn set_values_round_trip_through_the_env_file() {
+    let home = TempDir::new().unwrap();
+    fs::write(config_dir(&home).join(".env"), "EPICOR_DEFAULT_PLANT=MfgSys\n").unwrap();
+
+    for value in ["Pat O'Brien", "$HOME \\ \"quoted\""] {
+        let output = omni(&home)
+            .args(["config", "set", "EPICOR_DEFAULT_ASSIGNEE", value])
+            .output()
+            .unwrap();
+
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+        let output = omni(&home)
+            .args(["config", "show", "--format", "json"])
+            .output()
+            .unwrap();
+
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+        let settings: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(settings["EPICOR_DEFAULT_ASSIGNEE"], value);
+        assert_eq!(settings["EPICOR_DEFAULT_PLANT"], "MfgSys");
+    }
+}
+
+#[test]
+fn set_rejects_values_the_commands_would_reject() {
+    let home = TempDir::new().unwrap();
+    fs::write(config_dir(&home).join(".env"), "").unwrap();
+
+    for (name, value) in [
+        ("EPICOR_TIMEOUT_SECS", "thirty"),
+        ("EPICOR_MAX_RETRIES", "-1"),
+        ("EPICOR_API_VERSION", "2"),
+        ("EPICOR_COMPANY", "100/200"),
+    ] {
+        let output = omni(&home).args(["config", "set", name, value]).output().unwrap();
+
+        assert!(!output.status.success(), "{}={} was accepted", name, value);
+    }
+
+    assert_eq!(fs::read_to_string(config_dir(&home).join(".env")).unwrap(), "");
+}
+
+#[test]
+fn set_writes_every_setting_to_the_active_profile() {
+    let home = TempDir::new().unwrap();
+    let config_file = config_dir(&home).join("config.toml");
+    fs::write(&config_file, "default_profile = \"test\"\n\n[profiles.test]\n").unwrap();
+
+    for (name, value) in [("EPICOR_COMPANY", "200"), ("EPICOR_TIMEOUT_SECS", "45")] {
+        let output = omni(&home).args(["config", "set", name, value]).output().unwrap();
+
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    }
+
+    let config = fs::read_to_string(&config_file).unwrap();
+    assert!(config.contains("epicor_company = \"200\""), "{}", config);
+    assert!(config.contains("epicor_timeout_secs = \"45\""), "{}", config);
+}
+
+#[cfg(unix)]
+#[test]
+fn set_leaves_an_existing_env_file_readable_only_by_the_user() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let home = TempDir::new().unwrap();
+    let env_file = config_dir(&home).join(".env");
+    fs::write(&env_file, "").unwrap();
+    fs::set_permissions(&env_file, fs::Permissions::from_mode(0o644)).unwrap();
+
+    let output = omni(&home)
+        .args(["config", "set", "EPICOR_DEFAULT_PLANT", "MfgSys"])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(fs::metadata(&env_file).unwrap().permissions().mode() & 0o777, 0o600);
+}
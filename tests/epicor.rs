@@ -0,0 +1,141 @@
+mod common;
+
+use common::{cache_dir, case_status_body, function, omni_with_epicor, request_bodies, respond};
+use serde_json::{json, Value};
+use std::fs;
+use tempfile::TempDir;
+use wiremock::MockServer;
+
+fn success_body() -> Value {
+    json!({ "Error": false, "Message": "" })
+}
+
+#[tokio::test]
+async fn changing_a_case_clears_its_status_from_the_cache_dir() {
+    let server = MockServer::start().await;
+    respond(&server, &function("GetCaseStatus"), 200, case_status_body()).await;
+    respond(&server, &function("UpdateCaseQuote"), 200, success_body()).await;
+
+    let home = TempDir::new().unwrap();
+    let cached_status = cache_dir(&home).join("case-status").join("1234.json");
+
+    let output = omni_with_epicor(&server, &home)
+        .args(["epicor", "case", "get-status", "-n", "1234", "--cache-ttl", "60"])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(cached_status.exists());
+
+    let output = omni_with_epicor(&server, &home)
+        .args(["epicor", "case", "update-quote", "-c", "1234", "-n", "3"])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(!cached_status.exists());
+}
+
+#[tokio::test]
+async fn a_full_assignee_name_needs_no_company_section() {
+    let server = MockServer::start().await;
+    respond(
+        &server,
+        &function("CompleteTask"),
+        200,
+        json!({
+            "Error": false,
+            "Message": "",
+            "HasActiveTask": true,
+            "AuthorizedToCompleteTask": true,
+            "MultipleSalesRepMatches": false,
+            "NoSalesRepMatch": false
+        }),
+    )
+    .await;
+
+    let home = TempDir::new().unwrap();
+
+    let config_dir = home.path().join("config").join("omni");
+    fs::create_dir_all(&config_dir).unwrap();
+    fs::write(config_dir.join("config.toml"), "[company.200.assignees]\njd = \"Jane Doe\"\n")
+        .unwrap();
+
+    let output = omni_with_epicor(&server, &home)
+        .args(["epicor", "case", "complete-task", "-n", "1234", "-a", "Jane Doe"])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(
+        request_bodies(&server).await,
+        [json!({ "CaseNum": 1234, "AssignNextToName": "Jane Doe" })]
+    );
+}
+
+#[tokio::test]
+async fn quiet_hides_the_missing_env_file_notice() {
+    let server = MockServer::start().await;
+    respond(&server, &function("GetCaseStatus"), 200, case_status_body()).await;
+
+    let home = TempDir::new().unwrap();
+
+    let output = omni_with_epicor(&server, &home)
+        .args(["--quiet", "epicor", "case", "get-status", "-n", "1234"])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(output.stderr.is_empty(), "{}", String::from_utf8_lossy(&output.stderr));
+}
+
+#[tokio::test]
+async fn short_quiet_flag_hides_status_messages() {
+    let server = MockServer::start().await;
+    respond(&server, &function("UpdateCaseQuote"), 200, success_body()).await;
+
+    let home = TempDir::new().unwrap();
+
+    let output = omni_with_epicor(&server, &home)
+        .args(["epicor", "case", "update-quote", "-c", "1234", "-n", "3", "-q"])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(output.stdout.is_empty(), "{}", String::from_utf8_lossy(&output.stdout));
+    assert!(output.stderr.is_empty(), "{}", String::from_utf8_lossy(&output.stderr));
+}
+
+#[tokio::test]
+async fn dry_run_skips_the_vault_credentials_and_sends_nothing() {
+    let server = MockServer::start().await;
+    let home = TempDir::new().unwrap();
+
+    // There's no bw on PATH, so reading the credentials item would fail
+    let output = omni_with_epicor(&server, &home)
+        .env("EPICOR_CREDENTIALS_ITEM", "Epicor API")
+        .args(["--dry-run", "epicor", "case", "add-comment", "-n", "1234", "-c", "Parts ordered"])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Omni/AddCaseComment"));
+    assert!(request_bodies(&server).await.is_empty());
+}
+
+#[tokio::test]
+async fn ping_does_not_blame_the_api_key_for_a_403() {
+    let server = MockServer::start().await;
+    respond(&server, "/ERP/api/v2/odata/100/", 403, json!({})).await;
+
+    let home = TempDir::new().unwrap();
+
+    let output = omni_with_epicor(&server, &home).args(["epicor", "ping"]).output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(!output.status.success());
+
+    let api_key = stdout.lines().find(|line| line.starts_with("API Key:")).unwrap();
+    assert!(api_key.contains("unknown"), "{}", stdout);
+    assert!(api_key.contains("403 Forbidden"), "{}", stdout);
+}
@@ -0,0 +1,15 @@
+mod common;
+
+use common::omni;
+use tempfile::TempDir;
+
+#[test]
+fn bare_setup_without_a_terminal_lists_the_missing_options() {
+    let home = TempDir::new().unwrap();
+
+    let output = omni(&home).arg("setup").output().unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert!(!output.status.success());
+    assert!(stderr.contains("Missing required setup options: --bw-client-id"), "{}", stderr);
+}